@@ -0,0 +1,291 @@
+//! Debounced push button with long-press and double-click detection.
+//!
+//! [`Button`] doesn't read any pin: it is fed the raw level of the button with
+//! a timestamp in milliseconds, either from the GPIO interrupt on each edge
+//! ([`Button::on_edge`]) or by polling ([`Button::poll`]), and must be
+//! [`Button::update`]d regularly so a level that stopped bouncing is accepted
+//! and long presses are detected. Events are sent to a
+//! [`Channel`](crate::channel::Channel), so the application can take them
+//! without a critical section even when the button is fed from an interrupt
+//! handler.
+
+use crate::channel::Producer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonEvent {
+    Pressed,
+    Released,
+    /// The button has been held for this many milliseconds.
+    LongPress(u64),
+    /// The button was pressed again shortly after being released.
+    DoubleClick,
+}
+
+/// Timings of a [`Button`], in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ButtonConfig {
+    /// How long the raw level must stay the same to be accepted.
+    pub debounce: u64,
+    /// How long the button must be held for a [`ButtonEvent::LongPress`].
+    pub long_press: u64,
+    /// Longest release between two clicks of a [`ButtonEvent::DoubleClick`].
+    pub double_click: u64,
+}
+
+impl ButtonConfig {
+    pub const DEFAULT: Self = Self {
+        debounce: 20,
+        long_press: 800,
+        double_click: 300,
+    };
+}
+
+impl Default for ButtonConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Debouncing state machine of a button, sending its events to a
+/// [`Channel`](crate::channel::Channel) of `N` events.
+pub struct Button<const N: usize = 8> {
+    config: ButtonConfig,
+    // last raw level and when it was seen first
+    raw: bool,
+    raw_since: u64,
+    // debounced level
+    pressed: bool,
+    pressed_at: u64,
+    long_press_sent: bool,
+    double_click_sent: bool,
+    // end of the last click, for double clicks
+    released_at: Option<u64>,
+    events: Producer<ButtonEvent, N>,
+}
+
+impl<const N: usize> Button<N> {
+    pub const fn new(config: ButtonConfig, events: Producer<ButtonEvent, N>) -> Self {
+        Self {
+            config,
+            raw: false,
+            raw_since: 0,
+            pressed: false,
+            pressed_at: 0,
+            long_press_sent: false,
+            double_click_sent: false,
+            released_at: None,
+            events,
+        }
+    }
+
+    /// Records a raw edge, `pressed` being the level after it.
+    pub fn on_edge(&mut self, pressed: bool, now: u64) {
+        if pressed != self.raw {
+            self.raw = pressed;
+            self.raw_since = now;
+        }
+    }
+
+    /// Records a polled raw level and updates the state machine.
+    pub fn poll(&mut self, pressed: bool, now: u64) {
+        self.on_edge(pressed, now);
+        self.update(now);
+    }
+
+    /// Accepts the raw level once it stopped bouncing and detects long presses.
+    pub fn update(&mut self, now: u64) {
+        let settled = now.wrapping_sub(self.raw_since) >= self.config.debounce;
+
+        if settled && self.raw != self.pressed {
+            self.pressed = self.raw;
+
+            // timed from the edge that settled rather than from now
+            if self.pressed {
+                self.press(self.raw_since);
+            } else {
+                self.release(self.raw_since);
+            }
+        }
+
+        if self.pressed && !self.long_press_sent {
+            let held = now.wrapping_sub(self.pressed_at);
+            if held >= self.config.long_press {
+                self.long_press_sent = true;
+                self.push(ButtonEvent::LongPress(held));
+            }
+        }
+    }
+
+    /// Debounced level of the button.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    fn press(&mut self, at: u64) {
+        self.pressed_at = at;
+        self.long_press_sent = false;
+        self.push(ButtonEvent::Pressed);
+
+        self.double_click_sent = self
+            .released_at
+            .take()
+            .is_some_and(|released_at| at.wrapping_sub(released_at) <= self.config.double_click);
+        if self.double_click_sent {
+            self.push(ButtonEvent::DoubleClick);
+        }
+    }
+
+    fn release(&mut self, at: u64) {
+        self.push(ButtonEvent::Released);
+
+        // neither a long press nor the end of a double click start a double click
+        self.released_at = (!self.long_press_sent && !self.double_click_sent).then_some(at);
+    }
+
+    fn push(&mut self, event: ButtonEvent) {
+        // counted as dropped when the application doesn't keep up
+        self.events.push(event).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{boxed::Box, vec::Vec};
+
+    use super::*;
+    use crate::channel::{Channel, Consumer};
+
+    type Events = Consumer<ButtonEvent, 8>;
+
+    fn button() -> (Button, Events) {
+        let channel = Box::leak(Box::new(Channel::new()));
+        let (producer, consumer) = channel.split();
+        (Button::new(ButtonConfig::DEFAULT, producer), consumer)
+    }
+
+    // raw levels at given times, then updates every millisecond until `end`
+    fn replay(
+        (button, events): &mut (Button, Events),
+        edges: &[(u64, bool)],
+        end: u64,
+    ) -> Vec<ButtonEvent> {
+        let mut edges = edges.iter().peekable();
+        let mut received = Vec::new();
+        for now in 0..=end {
+            while let Some(&&(at, pressed)) = edges.peek() {
+                if at > now {
+                    break;
+                }
+                button.on_edge(pressed, at);
+                edges.next();
+            }
+            button.update(now);
+            received.extend(core::iter::from_fn(|| events.pop()));
+        }
+        received
+    }
+
+    #[test]
+    fn ignores_bounces() {
+        let mut button = button();
+        let edges = [
+            (100, true),
+            (102, false),
+            (105, true),
+            (107, false),
+            (110, true),
+            (300, false),
+            (303, true),
+            (306, false),
+        ];
+
+        assert_eq!(
+            replay(&mut button, &edges, 400),
+            [ButtonEvent::Pressed, ButtonEvent::Released]
+        );
+        assert!(!button.0.is_pressed());
+    }
+
+    #[test]
+    fn ignores_glitches_shorter_than_the_debounce() {
+        let mut button = button();
+        let edges = [(100, true), (110, false)];
+
+        assert_eq!(replay(&mut button, &edges, 400), []);
+    }
+
+    #[test]
+    fn clicks() {
+        let mut button = button();
+        let edges = [(100, true), (250, false)];
+
+        assert_eq!(
+            replay(&mut button, &edges, 300),
+            [ButtonEvent::Pressed, ButtonEvent::Released]
+        );
+    }
+
+    #[test]
+    fn long_press_is_timed_from_the_press() {
+        let mut button = button();
+        let edges = [(100, true), (2000, false)];
+
+        assert_eq!(
+            replay(&mut button, &edges, 2100),
+            [
+                ButtonEvent::Pressed,
+                ButtonEvent::LongPress(800),
+                ButtonEvent::Released
+            ]
+        );
+    }
+
+    #[test]
+    fn double_clicks() {
+        let mut button = button();
+        let edges = [(100, true), (200, false), (400, true), (500, false)];
+
+        assert_eq!(
+            replay(&mut button, &edges, 600),
+            [
+                ButtonEvent::Pressed,
+                ButtonEvent::Released,
+                ButtonEvent::Pressed,
+                ButtonEvent::DoubleClick,
+                ButtonEvent::Released
+            ]
+        );
+    }
+
+    #[test]
+    fn slow_clicks_are_not_double_clicks() {
+        let mut button = button();
+        let edges = [(100, true), (200, false), (600, true), (700, false)];
+
+        assert!(!replay(&mut button, &edges, 800).contains(&ButtonEvent::DoubleClick));
+    }
+
+    #[test]
+    fn long_press_does_not_start_a_double_click() {
+        let mut button = button();
+        let edges = [(100, true), (1000, false), (1100, true), (1200, false)];
+
+        assert!(!replay(&mut button, &edges, 1300).contains(&ButtonEvent::DoubleClick));
+    }
+
+    #[test]
+    fn polls() {
+        let mut button = button();
+        let (button, events) = &mut button;
+        for now in 0..200 {
+            button.poll((50..150).contains(&now), now);
+            assert_eq!(button.is_pressed(), (70..170).contains(&now));
+        }
+
+        assert_eq!(events.pop(), Some(ButtonEvent::Pressed));
+        assert_eq!(events.pop(), Some(ButtonEvent::Released));
+        assert_eq!(events.pop(), None);
+    }
+}
@@ -0,0 +1,326 @@
+//! Lock-free single-producer single-consumer event channel.
+//!
+//! Meant to pass events from an interrupt handler to the application without
+//! a critical section on either side: the producer only ever writes the tail
+//! index and the consumer the head index, which only needs atomic loads and
+//! stores as available on the ESP32-C3.
+
+use core::{
+    cell::{RefCell, UnsafeCell},
+    future::Future,
+    mem::MaybeUninit,
+    pin::Pin,
+    sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+
+use critical_section::Mutex;
+
+/// Channel holding up to `N` events, `N` being a power of two.
+pub struct Channel<T, const N: usize> {
+    buffer: [UnsafeCell<MaybeUninit<T>>; N],
+    // free running counters, the slot of an index is `index % N`
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    dropped: AtomicUsize,
+    split: Mutex<RefCell<bool>>,
+    waker: Mutex<RefCell<Option<Waker>>>,
+    // spares the producer a critical section when nobody awaits
+    waiting: AtomicBool,
+}
+
+// the producer and consumer handles make sure each side is used by one context
+unsafe impl<T: Send, const N: usize> Sync for Channel<T, N> {}
+
+impl<T, const N: usize> Channel<T, N> {
+    pub const fn new() -> Self {
+        assert!(
+            N.is_power_of_two(),
+            "channel capacity must be a power of two"
+        );
+
+        Self {
+            buffer: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+            split: Mutex::new(RefCell::new(false)),
+            waker: Mutex::new(RefCell::new(None)),
+            waiting: AtomicBool::new(false),
+        }
+    }
+
+    /// Hands out the two ends of the channel.
+    ///
+    /// # Panics
+    ///
+    /// If the channel was already split.
+    pub fn split(&'static self) -> (Producer<T, N>, Consumer<T, N>) {
+        let already_split = critical_section::with(|cs| self.split.borrow(cs).replace(true));
+        assert!(!already_split, "channel already split");
+
+        (Producer { channel: self }, Consumer { channel: self })
+    }
+
+    /// Number of events dropped because the channel was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.wrapping_sub(self.head.load(Ordering::Acquire))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, const N: usize> Default for Channel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Channel<T, N> {
+    fn drop(&mut self) {
+        let tail = *self.tail.get_mut();
+        let mut head = *self.head.get_mut();
+        while head != tail {
+            // SAFETY: the slots between head and tail hold written events
+            unsafe { self.buffer[head % N].get_mut().assume_init_drop() };
+            head = head.wrapping_add(1);
+        }
+    }
+}
+
+/// Sending end of a [`Channel`], typically owned by an interrupt handler.
+pub struct Producer<T: 'static, const N: usize> {
+    channel: &'static Channel<T, N>,
+}
+
+impl<T, const N: usize> Producer<T, N> {
+    /// Sends `event`, handing it back and counting it as dropped if the
+    /// channel is full.
+    pub fn push(&mut self, event: T) -> Result<(), T> {
+        let channel = self.channel;
+        let tail = channel.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(channel.head.load(Ordering::Acquire)) == N {
+            // only the producer writes the counter
+            let dropped = channel.dropped.load(Ordering::Relaxed);
+            channel
+                .dropped
+                .store(dropped.wrapping_add(1), Ordering::Relaxed);
+            return Err(event);
+        }
+
+        // SAFETY: the consumer doesn't read this slot until the tail moves past it
+        unsafe { (*channel.buffer[tail % N].get()).write(event) };
+        channel.tail.store(tail.wrapping_add(1), Ordering::Release);
+
+        // pairs with the fence of `NextEvent::poll`: either the consumer sees
+        // the new tail or the producer sees it waiting, a store then a load
+        // on each side not being ordered by acquire and release alone
+        fence(Ordering::SeqCst);
+        if channel.waiting.load(Ordering::Relaxed) {
+            critical_section::with(|cs| {
+                if let Some(waker) = channel.waker.borrow_ref_mut(cs).take() {
+                    waker.wake();
+                }
+            });
+        }
+        Ok(())
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.channel.dropped()
+    }
+}
+
+/// Receiving end of a [`Channel`], typically owned by the main loop or a task.
+pub struct Consumer<T: 'static, const N: usize> {
+    channel: &'static Channel<T, N>,
+}
+
+impl<T, const N: usize> Consumer<T, N> {
+    /// Takes the oldest event, if any.
+    pub fn pop(&mut self) -> Option<T> {
+        let channel = self.channel;
+        let head = channel.head.load(Ordering::Relaxed);
+        if head == channel.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        // SAFETY: the producer wrote this slot before moving the tail past it
+        let event = unsafe { (*channel.buffer[head % N].get()).assume_init_read() };
+        channel.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(event)
+    }
+
+    /// Waits for the next event, spinning.
+    pub fn next_event_blocking(&mut self) -> T {
+        loop {
+            if let Some(event) = self.pop() {
+                return event;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Waits for the next event, woken by the producer.
+    pub fn next_event(&mut self) -> NextEvent<'_, T, N> {
+        NextEvent { consumer: self }
+    }
+
+    /// Number of events dropped because the channel was full.
+    pub fn dropped(&self) -> usize {
+        self.channel.dropped()
+    }
+
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_empty()
+    }
+}
+
+/// Future returned by [`Consumer::next_event`].
+pub struct NextEvent<'a, T: 'static, const N: usize> {
+    consumer: &'a mut Consumer<T, N>,
+}
+
+impl<T, const N: usize> Future for NextEvent<'_, T, N> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let channel = self.consumer.channel;
+        if let Some(event) = self.consumer.pop() {
+            channel.waiting.store(false, Ordering::Release);
+            return Poll::Ready(event);
+        }
+
+        critical_section::with(|cs| {
+            channel.waker.borrow_ref_mut(cs).replace(cx.waker().clone());
+        });
+        channel.waiting.store(true, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+
+        // an event may have been pushed before the waker was registered
+        match self.consumer.pop() {
+            Some(event) => {
+                channel.waiting.store(false, Ordering::Release);
+                Poll::Ready(event)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{
+        boxed::Box,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::Wake,
+        thread,
+    };
+
+    use super::*;
+
+    fn split<T: Send, const N: usize>() -> (Producer<T, N>, Consumer<T, N>) {
+        Box::leak(Box::new(Channel::new())).split()
+    }
+
+    #[test]
+    fn passes_events_in_order() {
+        let (mut producer, mut consumer) = split::<u32, 4>();
+
+        assert_eq!(consumer.pop(), None);
+        for event in 0..3 {
+            producer.push(event).unwrap();
+        }
+        assert_eq!(consumer.len(), 3);
+        assert_eq!(consumer.pop(), Some(0));
+        producer.push(3).unwrap();
+        producer.push(4).unwrap();
+        assert_eq!(
+            core::iter::from_fn(|| consumer.pop()).collect::<std::vec::Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn drops_events_when_full() {
+        let (mut producer, mut consumer) = split::<u32, 2>();
+
+        producer.push(1).unwrap();
+        producer.push(2).unwrap();
+        assert_eq!(producer.push(3), Err(3));
+        assert_eq!(consumer.dropped(), 1);
+
+        assert_eq!(consumer.pop(), Some(1));
+        producer.push(4).unwrap();
+        assert_eq!(consumer.pop(), Some(2));
+        assert_eq!(consumer.pop(), Some(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "channel already split")]
+    fn splits_once() {
+        let channel: &'static Channel<u32, 2> = Box::leak(Box::new(Channel::new()));
+        let _ends = channel.split();
+        channel.split();
+    }
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn wakes_the_waiting_consumer() {
+        let (mut producer, mut consumer) = split::<u32, 4>();
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut next = core::pin::pin!(consumer.next_event());
+        assert_eq!(next.as_mut().poll(&mut cx), Poll::Pending);
+        producer.push(7).unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert_eq!(next.as_mut().poll(&mut cx), Poll::Ready(7));
+
+        // nobody waits any more
+        producer.push(8).unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn passes_events_between_threads() {
+        let (mut producer, mut consumer) = split::<u32, 8>();
+
+        let sender = thread::spawn(move || {
+            for event in 0..1000 {
+                while producer.push(event).is_err() {
+                    thread::yield_now();
+                }
+            }
+        });
+        for expected in 0..1000 {
+            assert_eq!(consumer.next_event_blocking(), expected);
+        }
+        sender.join().unwrap();
+    }
+}
@@ -0,0 +1,317 @@
+//! Line-based motor commands, e.g. over a serial console.
+//!
+//! Commands are case insensitive words separated by spaces, one per line:
+//!
+//! - `M1 <speed>`, `M2 <speed>`: runs a motor from `-100` to `100`,
+//! - `ARCADE <throttle> <steer>`, `TANK <left> <right>`: drives both wheels,
+//!   see [`DriveInput`],
+//! - `STOP`: stops both motors right away,
+//! - `STATUS`: asks for the motors speed.
+
+use core::fmt;
+
+use crate::motor::{self, DriveInput, ParseDriveError};
+
+/// Number of motors addressed by `M<n>`.
+pub const MOTORS: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Motor `motor`, from `0`, at `speed`.
+    Motor {
+        motor: u8,
+        speed: i8,
+    },
+    Drive(DriveInput),
+    Stop,
+    Status,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandError {
+    UnknownCommand,
+    /// `M<n>` naming a motor that doesn't exist.
+    UnknownMotor,
+    /// Missing, extra or non-numeric value.
+    InvalidValue,
+    /// Speed outside of `-100..=100`.
+    OutOfRange,
+    /// Line longer than the buffer, dropped.
+    LineTooLong,
+}
+
+impl CommandError {
+    /// Reply to send back, without line ending.
+    pub fn reply(&self) -> &'static str {
+        match self {
+            CommandError::UnknownCommand => "ERR unknown command",
+            CommandError::UnknownMotor => "ERR unknown motor",
+            CommandError::InvalidValue => "ERR invalid value",
+            CommandError::OutOfRange => "ERR speed out of range",
+            CommandError::LineTooLong => "ERR line too long",
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.reply())
+    }
+}
+
+impl From<ParseDriveError> for CommandError {
+    fn from(error: ParseDriveError) -> Self {
+        match error {
+            ParseDriveError::UnknownMode => CommandError::UnknownCommand,
+            ParseDriveError::InvalidValue => CommandError::InvalidValue,
+            ParseDriveError::OutOfRange => CommandError::OutOfRange,
+        }
+    }
+}
+
+/// Parses a line, without its line ending.
+pub fn parse(line: &str) -> Result<Command, CommandError> {
+    let mut words = line.split_ascii_whitespace();
+    let name = words.next().ok_or(CommandError::UnknownCommand)?;
+
+    let command = if name.eq_ignore_ascii_case("STOP") {
+        Command::Stop
+    } else if name.eq_ignore_ascii_case("STATUS") {
+        Command::Status
+    } else if name.eq_ignore_ascii_case("ARCADE") || name.eq_ignore_ascii_case("TANK") {
+        return Ok(Command::Drive(line.parse()?));
+    } else if let Some(number) = name.strip_prefix(['M', 'm']) {
+        let motor = match number.parse::<u8>() {
+            Ok(number @ 1..=MOTORS) => number - 1,
+            Ok(_) => return Err(CommandError::UnknownMotor),
+            Err(_) => return Err(CommandError::UnknownCommand),
+        };
+        let speed = motor::parse_speed(words.next())?;
+
+        Command::Motor { motor, speed }
+    } else {
+        return Err(CommandError::UnknownCommand);
+    };
+
+    if words.next().is_some() {
+        return Err(CommandError::InvalidValue);
+    }
+
+    Ok(command)
+}
+
+/// Bytes received gathered into lines of up to `N` bytes.
+pub struct LineBuffer<const N: usize = 32> {
+    buffer: [u8; N],
+    len: usize,
+    overflowed: bool,
+}
+
+impl<const N: usize> LineBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Takes a received byte, returns the line once it ends with `\n` or
+    /// `\r`. Blank lines are skipped.
+    pub fn push(&mut self, byte: u8) -> Option<Result<&str, CommandError>> {
+        if byte != b'\n' && byte != b'\r' {
+            if self.len < N {
+                self.buffer[self.len] = byte;
+                self.len += 1;
+            } else {
+                self.overflowed = true;
+            }
+            return None;
+        }
+
+        let len = core::mem::take(&mut self.len);
+        if core::mem::take(&mut self.overflowed) {
+            return Some(Err(CommandError::LineTooLong));
+        }
+
+        match core::str::from_utf8(&self.buffer[..len]) {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(Ok(line)),
+            Err(_) => Some(Err(CommandError::UnknownCommand)),
+        }
+    }
+}
+
+impl<const N: usize> Default for LineBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stops the motors when commands stop coming, e.g. the link was lost.
+pub struct Watchdog {
+    timeout: u64,
+    fed_at: Option<u64>,
+}
+
+impl Watchdog {
+    pub const fn new(timeout: u64) -> Self {
+        Self {
+            timeout,
+            fed_at: None,
+        }
+    }
+
+    /// Records a command received at `now`.
+    pub fn feed(&mut self, now: u64) {
+        self.fed_at = Some(now);
+    }
+
+    /// Whether no command came for the timeout, only `true` once until fed
+    /// again.
+    pub fn expired(&mut self, now: u64) -> bool {
+        let expired = self
+            .fed_at
+            .is_some_and(|fed_at| now.wrapping_sub(fed_at) >= self.timeout);
+        if expired {
+            self.fed_at = None;
+        }
+        expired
+    }
+
+    /// Stops timing until fed again, e.g. once the motors were stopped on
+    /// purpose.
+    pub fn disarm(&mut self) {
+        self.fed_at = None;
+    }
+
+    /// Whether commands are coming.
+    pub fn is_fed(&self) -> bool {
+        self.fed_at.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{string::String, vec::Vec};
+
+    use super::*;
+
+    // lines received from `bytes`
+    fn lines<const N: usize>(
+        buffer: &mut LineBuffer<N>,
+        bytes: &[u8],
+    ) -> Vec<Result<String, CommandError>> {
+        let mut lines = Vec::new();
+        for &byte in bytes {
+            if let Some(line) = buffer.push(byte) {
+                lines.push(line.map(String::from));
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            parse("M1 -40"),
+            Ok(Command::Motor {
+                motor: 0,
+                speed: -40
+            })
+        );
+        assert_eq!(
+            parse("m2 100"),
+            Ok(Command::Motor {
+                motor: 1,
+                speed: 100
+            })
+        );
+        assert_eq!(parse("  stop "), Ok(Command::Stop));
+        assert_eq!(parse("Status"), Ok(Command::Status));
+        assert_eq!(
+            parse("arcade 50 -10"),
+            Ok(Command::Drive(DriveInput::Arcade {
+                throttle: 50,
+                steer: -10
+            }))
+        );
+        assert_eq!(
+            parse("TANK 0 100"),
+            Ok(Command::Drive(DriveInput::Tank {
+                left: 0,
+                right: 100
+            }))
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(parse(""), Err(CommandError::UnknownCommand));
+        assert_eq!(parse("GO 10"), Err(CommandError::UnknownCommand));
+        assert_eq!(parse("MX 10"), Err(CommandError::UnknownCommand));
+        assert_eq!(parse("M0 10"), Err(CommandError::UnknownMotor));
+        assert_eq!(parse("M3 10"), Err(CommandError::UnknownMotor));
+        assert_eq!(parse("M1"), Err(CommandError::InvalidValue));
+        assert_eq!(parse("M1 fast"), Err(CommandError::InvalidValue));
+        assert_eq!(parse("M1 10 20"), Err(CommandError::InvalidValue));
+        assert_eq!(parse("STOP now"), Err(CommandError::InvalidValue));
+        assert_eq!(parse("M1 101"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("M1 -32768"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("TANK -32768 0"), Err(CommandError::OutOfRange));
+        assert_eq!(CommandError::OutOfRange.reply(), "ERR speed out of range");
+    }
+
+    #[test]
+    fn gathers_lines() {
+        let mut buffer: LineBuffer = LineBuffer::new();
+
+        assert_eq!(
+            lines(&mut buffer, b"M1 10\r\n\r\nSTOP\nSTA"),
+            [Ok(String::from("M1 10")), Ok(String::from("STOP"))]
+        );
+        assert_eq!(lines(&mut buffer, b"TUS\n"), [Ok(String::from("STATUS"))]);
+        assert_eq!(
+            lines(&mut buffer, b"\xff\n"),
+            [Err(CommandError::UnknownCommand)]
+        );
+    }
+
+    #[test]
+    fn drops_lines_too_long() {
+        let mut buffer: LineBuffer<4> = LineBuffer::new();
+
+        assert_eq!(
+            lines(&mut buffer, b"M1 100\nSTOP\n"),
+            [Err(CommandError::LineTooLong), Ok(String::from("STOP"))]
+        );
+    }
+
+    #[test]
+    fn watchdog_expires_once_without_commands() {
+        let mut watchdog = Watchdog::new(1000);
+        assert!(!watchdog.expired(5000));
+
+        watchdog.feed(100);
+        assert!(watchdog.is_fed());
+        assert!(!watchdog.expired(1099));
+        watchdog.feed(900);
+        assert!(!watchdog.expired(1899));
+        assert!(watchdog.expired(1900));
+        assert!(!watchdog.is_fed());
+        assert!(!watchdog.expired(5000));
+    }
+
+    #[test]
+    fn disarmed_watchdog_does_not_expire() {
+        let mut watchdog = Watchdog::new(1000);
+        watchdog.feed(100);
+
+        watchdog.disarm();
+        assert!(!watchdog.is_fed());
+        assert!(!watchdog.expired(1100));
+        assert!(!watchdog.expired(5000));
+    }
+}
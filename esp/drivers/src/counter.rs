@@ -0,0 +1,145 @@
+//! Bounded up/down counter.
+
+/// What happens when the counter goes past one of its bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Continue from the other bound.
+    Wrap,
+    /// Stay on the bound.
+    Saturate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterCommand {
+    Increment,
+    Decrement,
+    /// Go back to the lower bound.
+    Reset,
+}
+
+/// Counter between `min` and `max` included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Counter {
+    min: i32,
+    max: i32,
+    overflow: Overflow,
+    value: i32,
+}
+
+impl Counter {
+    /// Counter starting at `min`.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`.
+    pub const fn new(min: i32, max: i32, overflow: Overflow) -> Self {
+        assert!(min <= max, "counter lower bound above its upper bound");
+
+        Self {
+            min,
+            max,
+            overflow,
+            value: min,
+        }
+    }
+
+    /// Starts from `value` clamped to the bounds, e.g. one restored from flash.
+    pub fn with_value(self, value: i32) -> Self {
+        Self {
+            value: value.clamp(self.min, self.max),
+            ..self
+        }
+    }
+
+    /// Applies `command`, returns whether the value changed.
+    pub fn apply(&mut self, command: CounterCommand) -> bool {
+        let previous = self.value;
+
+        self.value = match (command, self.overflow) {
+            (CounterCommand::Increment, _) if self.value < self.max => self.value + 1,
+            (CounterCommand::Increment, Overflow::Wrap) => self.min,
+            (CounterCommand::Decrement, _) if self.value > self.min => self.value - 1,
+            (CounterCommand::Decrement, Overflow::Wrap) => self.max,
+            (CounterCommand::Reset, _) => self.min,
+            (_, Overflow::Saturate) => self.value,
+        };
+
+        self.value != previous
+    }
+
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+
+    pub fn min(&self) -> i32 {
+        self.min
+    }
+
+    pub fn max(&self) -> i32 {
+        self.max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_at_both_bounds() {
+        let mut counter = Counter::new(0, 2, Overflow::Wrap).with_value(2);
+
+        assert!(counter.apply(CounterCommand::Increment));
+        assert_eq!(counter.value(), 0);
+        assert!(counter.apply(CounterCommand::Decrement));
+        assert_eq!(counter.value(), 2);
+        assert!(counter.apply(CounterCommand::Decrement));
+        assert_eq!(counter.value(), 1);
+    }
+
+    #[test]
+    fn saturates_at_both_bounds() {
+        let mut counter = Counter::new(-1, 1, Overflow::Saturate);
+        assert_eq!(counter.value(), -1);
+
+        assert!(!counter.apply(CounterCommand::Decrement));
+        assert_eq!(counter.value(), -1);
+        assert!(counter.apply(CounterCommand::Increment));
+        assert!(counter.apply(CounterCommand::Increment));
+        assert!(!counter.apply(CounterCommand::Increment));
+        assert_eq!(counter.value(), 1);
+    }
+
+    #[test]
+    fn resets_to_the_lower_bound() {
+        let mut counter = Counter::new(3, 9, Overflow::Wrap).with_value(7);
+
+        assert!(counter.apply(CounterCommand::Reset));
+        assert_eq!(counter.value(), 3);
+        assert!(!counter.apply(CounterCommand::Reset));
+    }
+
+    #[test]
+    fn clamps_restored_values() {
+        let counter = Counter::new(0, 9, Overflow::Saturate);
+
+        assert_eq!(counter.with_value(5).value(), 5);
+        assert_eq!(counter.with_value(42).value(), 9);
+        assert_eq!(counter.with_value(i32::MIN).value(), 0);
+        assert_eq!((counter.min(), counter.max()), (0, 9));
+    }
+
+    #[test]
+    fn single_value_never_changes() {
+        let mut counter = Counter::new(4, 4, Overflow::Wrap);
+
+        assert!(!counter.apply(CounterCommand::Increment));
+        assert!(!counter.apply(CounterCommand::Decrement));
+        assert_eq!(counter.value(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "counter lower bound above its upper bound")]
+    fn bounds_are_ordered() {
+        Counter::new(1, 0, Overflow::Wrap);
+    }
+}
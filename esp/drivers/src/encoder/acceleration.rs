@@ -0,0 +1,184 @@
+//! Scaling of encoder turns by turning speed.
+
+use super::Turn;
+
+/// Shape of the multiplier between slow and fast turning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    Linear,
+    /// Stays close to one step per detent longer, then ramps up quickly.
+    Quadratic,
+}
+
+/// Turning speeds, as milliseconds between two detents, and multipliers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccelerationConfig {
+    /// Detents further apart than this count for exactly one step.
+    pub slow: u64,
+    /// Detents this close or closer count for `max_multiplier` steps.
+    pub fast: u64,
+    pub max_multiplier: u32,
+    pub curve: Curve,
+}
+
+impl AccelerationConfig {
+    pub const DEFAULT: Self = Self {
+        slow: 120,
+        fast: 15,
+        max_multiplier: 50,
+        curve: Curve::Quadratic,
+    };
+}
+
+impl Default for AccelerationConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Turns detents into steps, more of them the faster the encoder turns.
+///
+/// The first detent after a pause or a change of direction always counts for
+/// a single step so that fine adjustments stay exact.
+pub struct Acceleration {
+    config: AccelerationConfig,
+    last: Option<(Turn, u64)>,
+}
+
+impl Acceleration {
+    pub const fn new(config: AccelerationConfig) -> Self {
+        Self { config, last: None }
+    }
+
+    /// Signed steps for a detent turned at `now`, in milliseconds.
+    pub fn apply(&mut self, turn: Turn, now: u64) -> i32 {
+        let interval = match self.last.replace((turn, now)) {
+            Some((last_turn, at)) if last_turn == turn => now.wrapping_sub(at),
+            _ => u64::MAX,
+        };
+
+        turn.delta() * self.multiplier(interval) as i32
+    }
+
+    /// Steps per detent for detents `interval` milliseconds apart.
+    pub fn multiplier(&self, interval: u64) -> u32 {
+        let AccelerationConfig {
+            slow,
+            fast,
+            max_multiplier,
+            curve,
+        } = self.config;
+
+        if interval >= slow || slow <= fast {
+            return 1;
+        }
+
+        // speed from 0 when slow to 1000 when fast
+        let speed = (slow - interval.max(fast)) * 1000 / (slow - fast);
+        let speed = match curve {
+            Curve::Linear => speed,
+            Curve::Quadratic => speed * speed / 1000,
+        };
+
+        1 + (max_multiplier.saturating_sub(1) as u64 * speed / 1000) as u32
+    }
+
+    /// Forgets the last detent, the next one counts for a single step.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+impl Default for Acceleration {
+    fn default() -> Self {
+        Self::new(AccelerationConfig::DEFAULT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINEAR: AccelerationConfig = AccelerationConfig {
+        curve: Curve::Linear,
+        ..AccelerationConfig::DEFAULT
+    };
+
+    #[test]
+    fn slow_turns_stay_exact() {
+        let acceleration = Acceleration::default();
+
+        assert_eq!(acceleration.multiplier(120), 1);
+        assert_eq!(acceleration.multiplier(500), 1);
+        assert_eq!(acceleration.multiplier(u64::MAX), 1);
+    }
+
+    #[test]
+    fn fast_turns_reach_the_max() {
+        let acceleration = Acceleration::default();
+
+        assert_eq!(acceleration.multiplier(15), 50);
+        assert_eq!(acceleration.multiplier(0), 50);
+    }
+
+    #[test]
+    fn curves() {
+        let linear = Acceleration::new(LINEAR);
+        let quadratic = Acceleration::default();
+
+        assert_eq!(linear.multiplier(68), 25);
+        assert_eq!(quadratic.multiplier(68), 13);
+        assert_eq!(linear.multiplier(110), 5);
+        assert_eq!(quadratic.multiplier(110), 1);
+
+        for curve in [linear, quadratic] {
+            let mut previous = u32::MAX;
+            for interval in 0..=150 {
+                let multiplier = curve.multiplier(interval);
+                assert!(multiplier <= previous, "faster at {interval} ms");
+                assert!((1..=50).contains(&multiplier));
+                previous = multiplier;
+            }
+        }
+    }
+
+    #[test]
+    fn accelerates_detents_in_a_row() {
+        let mut acceleration = Acceleration::new(LINEAR);
+
+        assert_eq!(acceleration.apply(Turn::Right, 1000), 1);
+        assert_eq!(acceleration.apply(Turn::Right, 1015), 50);
+        assert_eq!(acceleration.apply(Turn::Right, 1083), 25);
+        assert_eq!(acceleration.apply(Turn::Right, 1500), 1);
+    }
+
+    #[test]
+    fn a_change_of_direction_starts_slow() {
+        let mut acceleration = Acceleration::new(LINEAR);
+
+        acceleration.apply(Turn::Right, 1000);
+        assert_eq!(acceleration.apply(Turn::Right, 1010), 50);
+        assert_eq!(acceleration.apply(Turn::Left, 1020), -1);
+        assert_eq!(acceleration.apply(Turn::Left, 1030), -50);
+    }
+
+    #[test]
+    fn reset_forgets_the_last_detent() {
+        let mut acceleration = Acceleration::new(LINEAR);
+
+        acceleration.apply(Turn::Left, 1000);
+        acceleration.reset();
+        assert_eq!(acceleration.apply(Turn::Left, 1010), -1);
+    }
+
+    #[test]
+    fn misconfigured_speeds_stay_exact() {
+        let acceleration = Acceleration::new(AccelerationConfig {
+            slow: 10,
+            fast: 20,
+            ..LINEAR
+        });
+
+        assert_eq!(acceleration.multiplier(0), 1);
+    }
+}
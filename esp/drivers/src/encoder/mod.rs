@@ -0,0 +1,30 @@
+//! Rotary encoders.
+
+mod acceleration;
+mod quadrature;
+
+pub use acceleration::{Acceleration, AccelerationConfig, Curve};
+pub use quadrature::{QuadratureDecoder, StepsPerDetent};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Turn {
+    Left,
+    Right,
+}
+
+impl Turn {
+    /// `1` to the right, `-1` to the left.
+    pub fn delta(self) -> i32 {
+        match self {
+            Turn::Left => -1,
+            Turn::Right => 1,
+        }
+    }
+}
+
+/// A detent turned at `at` milliseconds, as sent by an interrupt handler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TurnEvent {
+    pub turn: Turn,
+    pub at: u64,
+}
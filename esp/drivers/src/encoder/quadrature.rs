@@ -0,0 +1,216 @@
+//! Gray-code quadrature decoding.
+
+use super::Turn;
+
+/// Quadrature steps between two detents of the encoder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepsPerDetent {
+    One = 1,
+    Two = 2,
+    Four = 4,
+}
+
+// direction of each transition, indexed by `old << 2 | new` where a state is
+// `clock << 1 | data`, turning right clock goes first: 00 -> 10 -> 11 -> 01
+const TRANSITIONS: [i8; 16] = [
+    0, -1, 1, 0, //
+    1, 0, 0, -1, //
+    -1, 0, 0, 1, //
+    0, 1, -1, 0, //
+];
+
+// position of each state in the Gray-code cycle
+const PHASES: [u8; 4] = [0, 3, 1, 2];
+
+/// Decoder of the clock and data lines of an encoder, fed from the edges of
+/// both lines.
+///
+/// Steps are accumulated and a [`Turn`] is reported when the encoder reaches
+/// the next detent, so contact bounce going back and forth between two states
+/// cancels out. Transitions where both lines changed at once can't tell the
+/// direction and are rejected.
+pub struct QuadratureDecoder {
+    state: u8,
+    steps_per_detent: u8,
+    // phase of the detents in the Gray-code cycle
+    rest_phase: u8,
+    steps: i8,
+    position: i32,
+    invalid: u32,
+}
+
+impl QuadratureDecoder {
+    /// Decoder of an encoder resting on a detent with these line levels.
+    pub const fn new(steps_per_detent: StepsPerDetent, clock: bool, data: bool) -> Self {
+        let state = (clock as u8) << 1 | data as u8;
+
+        Self {
+            state,
+            steps_per_detent: steps_per_detent as u8,
+            rest_phase: PHASES[state as usize] % steps_per_detent as u8,
+            steps: 0,
+            position: 0,
+            invalid: 0,
+        }
+    }
+
+    /// Feeds the levels of both lines after an edge on either of them.
+    pub fn update(&mut self, clock: bool, data: bool) -> Option<Turn> {
+        let state = (clock as u8) << 1 | data as u8;
+        let old = self.state;
+        if state == old {
+            return None;
+        }
+        self.state = state;
+
+        if old ^ state == 0b11 {
+            // the steps so far can't be trusted, and noise jumping over the
+            // detents would pile them up
+            self.invalid = self.invalid.wrapping_add(1);
+            self.steps = 0;
+            return None;
+        }
+        self.steps += TRANSITIONS[(old << 2 | state) as usize];
+
+        if PHASES[state as usize] % self.steps_per_detent != self.rest_phase {
+            return None;
+        }
+
+        // on a detent, count a turn if it was mostly reached from the other one
+        let threshold = (self.steps_per_detent as i8 / 2).max(1);
+        let steps = core::mem::take(&mut self.steps);
+        let turn = if steps >= threshold {
+            Turn::Right
+        } else if steps <= -threshold {
+            Turn::Left
+        } else {
+            return None;
+        };
+
+        self.position = self.position.wrapping_add(turn.delta());
+        Some(turn)
+    }
+
+    /// Detents turned since the start, positive to the right.
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: i32) {
+        self.position = position;
+    }
+
+    /// Number of rejected transitions, a sign of missed edges.
+    pub fn invalid_transitions(&self) -> u32 {
+        self.invalid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    // clock and data levels turning one detent of four steps right
+    const RIGHT: [(bool, bool); 4] = [(true, false), (true, true), (false, true), (false, false)];
+    const LEFT: [(bool, bool); 4] = [(false, true), (true, true), (true, false), (false, false)];
+
+    fn replay(decoder: &mut QuadratureDecoder, edges: &[(bool, bool)]) -> Vec<Turn> {
+        edges
+            .iter()
+            .filter_map(|&(clock, data)| decoder.update(clock, data))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_clean_turns() {
+        let mut decoder = QuadratureDecoder::new(StepsPerDetent::Four, false, false);
+
+        assert_eq!(replay(&mut decoder, &RIGHT), [Turn::Right]);
+        assert_eq!(replay(&mut decoder, &RIGHT), [Turn::Right]);
+        assert_eq!(replay(&mut decoder, &LEFT), [Turn::Left]);
+        assert_eq!(decoder.position(), 1);
+        assert_eq!(decoder.invalid_transitions(), 0);
+    }
+
+    #[test]
+    fn counts_one_turn_per_detent() {
+        let mut one = QuadratureDecoder::new(StepsPerDetent::One, false, false);
+        let mut two = QuadratureDecoder::new(StepsPerDetent::Two, false, false);
+
+        assert_eq!(replay(&mut one, &RIGHT), [Turn::Right; 4]);
+        assert_eq!(replay(&mut two, &RIGHT), [Turn::Right; 2]);
+        assert_eq!(replay(&mut two, &LEFT), [Turn::Left; 2]);
+        assert_eq!(one.position(), 4);
+        assert_eq!(two.position(), 0);
+    }
+
+    #[test]
+    fn rests_on_the_starting_levels() {
+        // detents where both lines are high
+        let mut decoder = QuadratureDecoder::new(StepsPerDetent::Four, true, true);
+        let edges = [(false, true), (false, false), (true, false), (true, true)];
+
+        assert_eq!(replay(&mut decoder, &edges), [Turn::Right]);
+    }
+
+    #[test]
+    fn ignores_bounces() {
+        let mut decoder = QuadratureDecoder::new(StepsPerDetent::Four, false, false);
+        // recorded from a worn encoder, the clock bouncing on its first edge
+        // and the data on its last one
+        let edges = [
+            (true, false),
+            (false, false),
+            (true, false),
+            (false, false),
+            (true, false),
+            (true, true),
+            (false, true),
+            (false, false),
+            (false, true),
+            (false, false),
+        ];
+
+        assert_eq!(replay(&mut decoder, &edges), [Turn::Right]);
+        assert_eq!(decoder.position(), 1);
+    }
+
+    #[test]
+    fn ignores_a_turn_started_then_abandoned() {
+        let mut decoder = QuadratureDecoder::new(StepsPerDetent::Four, false, false);
+        let edges = [(true, false), (true, true), (true, false), (false, false)];
+
+        assert_eq!(replay(&mut decoder, &edges), []);
+        assert_eq!(decoder.position(), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_transitions() {
+        let mut decoder = QuadratureDecoder::new(StepsPerDetent::Four, false, false);
+        // both lines changing at once, then a clean turn
+        let mut edges = Vec::from([(true, true), (false, false)]);
+        edges.extend(LEFT);
+
+        assert_eq!(replay(&mut decoder, &edges), [Turn::Left]);
+        assert_eq!(decoder.invalid_transitions(), 2);
+    }
+
+    #[test]
+    fn survives_noise_jumping_over_the_detents() {
+        let mut decoder = QuadratureDecoder::new(StepsPerDetent::Four, false, false);
+        decoder.update(true, false);
+
+        // two steps right then a jump back, never resting on a detent
+        for _ in 0..200 {
+            replay(&mut decoder, &[(true, true), (false, true), (true, false)]);
+        }
+
+        assert_eq!(decoder.invalid_transitions(), 200);
+        assert_eq!(decoder.position(), 0);
+        assert_eq!(replay(&mut decoder, &RIGHT[1..]), [Turn::Right]);
+    }
+}
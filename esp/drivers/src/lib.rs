@@ -0,0 +1,18 @@
+//! Hardware-agnostic drivers shared by the sandbox firmwares.
+//!
+//! Everything here is written against the `embedded-hal` traits only, so it
+//! builds for the ESP32-C3 as well as for the host.
+#![no_std]
+
+pub mod button;
+pub mod channel;
+pub mod command;
+pub mod counter;
+pub mod encoder;
+pub mod menu;
+#[cfg(test)]
+mod mock;
+pub mod motor;
+pub mod seven_segment;
+pub mod sonar;
+pub mod storage;
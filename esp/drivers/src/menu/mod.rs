@@ -0,0 +1,503 @@
+//! Menus driven by a rotary encoder with a push button.
+//!
+//! A menu is a static tree of [`Item`]s. The values edited through it live in
+//! the [`Menu`], indexed by the `id` of their item, and the navigation is a
+//! pure state machine fed with [`Input`]s: turning moves the cursor or
+//! changes the value being edited, a click selects and a long press goes
+//! back.
+
+mod render;
+
+pub use render::render;
+
+use crate::{
+    button::ButtonEvent,
+    encoder::{Turn, TurnEvent},
+};
+
+/// An entry of a menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Item<'a> {
+    Submenu {
+        label: &'a str,
+        items: &'a [Item<'a>],
+    },
+    /// Number from `min` to `max` changed by `step` per detent.
+    Number {
+        label: &'a str,
+        id: usize,
+        min: i32,
+        max: i32,
+        step: i32,
+    },
+    /// On/off value, `0` or `1`.
+    Toggle { label: &'a str, id: usize },
+    /// Index of one of `options`.
+    Choice {
+        label: &'a str,
+        id: usize,
+        options: &'a [&'a str],
+    },
+    /// Reported as [`MenuEvent::Action`] when selected.
+    Action { label: &'a str, id: usize },
+}
+
+impl<'a> Item<'a> {
+    pub fn label(&self) -> &'a str {
+        match *self {
+            Item::Submenu { label, .. }
+            | Item::Number { label, .. }
+            | Item::Toggle { label, .. }
+            | Item::Choice { label, .. }
+            | Item::Action { label, .. } => label,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    /// Detents turned, positive to the right.
+    Rotate(i32),
+    Select,
+    Back,
+}
+
+impl Input {
+    pub fn from_turn(turn: Turn) -> Self {
+        Input::Rotate(turn.delta())
+    }
+
+    /// A click selects, a long press goes back.
+    pub fn from_button(event: ButtonEvent) -> Option<Self> {
+        match event {
+            ButtonEvent::Released => Some(Input::Select),
+            ButtonEvent::LongPress(_) => Some(Input::Back),
+            _ => None,
+        }
+    }
+}
+
+impl From<TurnEvent> for Input {
+    fn from(event: TurnEvent) -> Self {
+        Input::from_turn(event.turn)
+    }
+}
+
+/// What the application must react to after an [`Input`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuEvent {
+    Changed {
+        id: usize,
+        value: i32,
+    },
+    Action {
+        id: usize,
+    },
+    /// Back was pressed on the root menu.
+    Exited,
+}
+
+/// Navigation in a tree of menus up to `DEPTH` levels deep, holding `V`
+/// values.
+pub struct Menu<'a, const V: usize, const DEPTH: usize = 4> {
+    root: &'a [Item<'a>],
+    title: &'a str,
+    values: [i32; V],
+    // cursor in each open menu
+    path: [usize; DEPTH],
+    depth: usize,
+    editing: bool,
+    // a button released after a long press isn't a click
+    ignore_release: bool,
+}
+
+impl<'a, const V: usize, const DEPTH: usize> Menu<'a, V, DEPTH> {
+    pub const fn new(title: &'a str, root: &'a [Item<'a>]) -> Self {
+        Self {
+            root,
+            title,
+            values: [0; V],
+            path: [0; DEPTH],
+            depth: 0,
+            editing: false,
+            ignore_release: false,
+        }
+    }
+
+    /// Sets the initial values, indexed by item id.
+    pub fn with_values(self, values: [i32; V]) -> Self {
+        Self { values, ..self }
+    }
+
+    pub fn handle(&mut self, input: Input) -> Option<MenuEvent> {
+        let item = self.selected()?;
+
+        if self.editing {
+            return match input {
+                Input::Rotate(detents) => self.edit(item, detents),
+                Input::Select | Input::Back => {
+                    self.editing = false;
+                    None
+                }
+            };
+        }
+
+        match input {
+            Input::Rotate(detents) => {
+                let len = self.items().len() as i32;
+                let cursor = &mut self.path[self.depth];
+                // whole turns dropped first, so that any number of detents
+                // lands where it should
+                *cursor = (*cursor as i32)
+                    .saturating_add(detents.rem_euclid(len))
+                    .rem_euclid(len) as usize;
+                None
+            }
+            Input::Select => self.select(item),
+            Input::Back if self.depth == 0 => Some(MenuEvent::Exited),
+            Input::Back => {
+                self.depth -= 1;
+                None
+            }
+        }
+    }
+
+    /// Feeds a button event, see [`Input::from_button`].
+    pub fn handle_button(&mut self, event: ButtonEvent) -> Option<MenuEvent> {
+        match event {
+            ButtonEvent::Pressed => self.ignore_release = false,
+            ButtonEvent::LongPress(_) => self.ignore_release = true,
+            ButtonEvent::Released if self.ignore_release => return None,
+            _ => (),
+        }
+
+        self.handle(Input::from_button(event)?)
+    }
+
+    pub fn value(&self, id: usize) -> i32 {
+        self.values[id]
+    }
+
+    pub fn set_value(&mut self, id: usize, value: i32) {
+        self.values[id] = value;
+    }
+
+    pub fn values(&self) -> &[i32; V] {
+        &self.values
+    }
+
+    /// Title of the open menu.
+    pub fn title(&self) -> &'a str {
+        if self.depth == 0 {
+            return self.title;
+        }
+
+        let mut items = self.root;
+        let mut title = self.title;
+        for &cursor in &self.path[..self.depth] {
+            if let Item::Submenu {
+                label,
+                items: children,
+            } = items[cursor]
+            {
+                title = label;
+                items = children;
+            }
+        }
+        title
+    }
+
+    /// Items of the open menu.
+    pub fn items(&self) -> &'a [Item<'a>] {
+        let mut items = self.root;
+        for &cursor in &self.path[..self.depth] {
+            if let Item::Submenu {
+                items: children, ..
+            } = items[cursor]
+            {
+                items = children;
+            }
+        }
+        items
+    }
+
+    /// Position of the cursor in the open menu.
+    pub fn cursor(&self) -> usize {
+        self.path[self.depth]
+    }
+
+    pub fn selected(&self) -> Option<Item<'a>> {
+        self.items().get(self.cursor()).copied()
+    }
+
+    /// Whether the selected item's value is being edited.
+    pub fn is_editing(&self) -> bool {
+        self.editing
+    }
+
+    /// How many menus are open above the root one.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn select(&mut self, item: Item<'a>) -> Option<MenuEvent> {
+        match item {
+            Item::Submenu { items, .. } => {
+                if self.depth + 1 < DEPTH && !items.is_empty() {
+                    self.depth += 1;
+                    self.path[self.depth] = 0;
+                }
+                None
+            }
+            Item::Number { .. } | Item::Choice { .. } => {
+                self.editing = true;
+                None
+            }
+            Item::Toggle { id, .. } => {
+                let value = (self.values[id] == 0) as i32;
+                self.change(id, value)
+            }
+            Item::Action { id, .. } => Some(MenuEvent::Action { id }),
+        }
+    }
+
+    fn edit(&mut self, item: Item<'a>, detents: i32) -> Option<MenuEvent> {
+        match item {
+            Item::Number {
+                id, min, max, step, ..
+            } => {
+                let value = self.values[id]
+                    .saturating_add(detents.saturating_mul(step))
+                    .clamp(min, max);
+                self.change(id, value)
+            }
+            Item::Choice { id, options, .. } if !options.is_empty() => {
+                let len = options.len() as i32;
+                let value = self.values[id]
+                    .saturating_add(detents.rem_euclid(len))
+                    .rem_euclid(len);
+                self.change(id, value)
+            }
+            _ => None,
+        }
+    }
+
+    fn change(&mut self, id: usize, value: i32) -> Option<MenuEvent> {
+        if self.values[id] == value {
+            return None;
+        }
+
+        self.values[id] = value;
+        Some(MenuEvent::Changed { id, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEED: usize = 0;
+    const BEEP: usize = 1;
+    const UNIT: usize = 2;
+    const RESET: usize = 3;
+
+    const DISPLAY: &[Item] = &[Item::Choice {
+        label: "Unit",
+        id: UNIT,
+        options: &["cm", "inch", "ft"],
+    }];
+
+    const ROOT: &[Item] = &[
+        Item::Number {
+            label: "Speed",
+            id: SPEED,
+            min: 0,
+            max: 100,
+            step: 5,
+        },
+        Item::Toggle {
+            label: "Beep",
+            id: BEEP,
+        },
+        Item::Submenu {
+            label: "Display",
+            items: DISPLAY,
+        },
+        Item::Action {
+            label: "Reset",
+            id: RESET,
+        },
+    ];
+
+    fn menu() -> Menu<'static, 4> {
+        Menu::new("Settings", ROOT).with_values([50, 0, 0, 0])
+    }
+
+    #[test]
+    fn cursor_wraps_around() {
+        let mut menu = menu();
+
+        assert_eq!(menu.handle(Input::Rotate(-1)), None);
+        assert_eq!(menu.cursor(), 3);
+        assert_eq!(menu.selected().map(|item| item.label()), Some("Reset"));
+
+        menu.handle(Input::Rotate(6));
+        assert_eq!(menu.cursor(), 1);
+    }
+
+    #[test]
+    fn edits_numbers_within_their_range() {
+        let mut menu = menu();
+
+        assert_eq!(menu.handle(Input::Select), None);
+        assert!(menu.is_editing());
+        assert_eq!(
+            menu.handle(Input::Rotate(3)),
+            Some(MenuEvent::Changed {
+                id: SPEED,
+                value: 65
+            })
+        );
+        assert_eq!(
+            menu.handle(Input::Rotate(20)),
+            Some(MenuEvent::Changed {
+                id: SPEED,
+                value: 100
+            })
+        );
+        // already at the max
+        assert_eq!(menu.handle(Input::Rotate(1)), None);
+        assert_eq!(
+            menu.handle(Input::Rotate(i32::MIN)),
+            Some(MenuEvent::Changed {
+                id: SPEED,
+                value: 0
+            })
+        );
+
+        // turning moves the cursor again once done
+        menu.handle(Input::Select);
+        assert!(!menu.is_editing());
+        menu.handle(Input::Rotate(1));
+        assert_eq!(menu.cursor(), 1);
+        assert_eq!(menu.value(SPEED), 0);
+    }
+
+    #[test]
+    fn survives_extreme_rotations() {
+        let mut menu = menu();
+
+        // i32::MAX is 3 past a multiple of 4 items
+        menu.handle(Input::Rotate(i32::MAX));
+        assert_eq!(menu.cursor(), 3);
+        menu.handle(Input::Rotate(i32::MIN));
+        assert_eq!(menu.cursor(), 3);
+
+        // i32::MIN is 1 past a multiple of 3 options
+        menu.handle(Input::Rotate(-1));
+        menu.handle(Input::Select);
+        menu.handle(Input::Select);
+        assert_eq!(
+            menu.handle(Input::Rotate(i32::MIN)),
+            Some(MenuEvent::Changed { id: UNIT, value: 1 })
+        );
+        assert_eq!(
+            menu.handle(Input::Rotate(i32::MAX)),
+            Some(MenuEvent::Changed { id: UNIT, value: 2 })
+        );
+    }
+
+    #[test]
+    fn toggles_and_actions_are_reported_on_select() {
+        let mut menu = menu();
+
+        menu.handle(Input::Rotate(1));
+        assert_eq!(
+            menu.handle(Input::Select),
+            Some(MenuEvent::Changed { id: BEEP, value: 1 })
+        );
+        assert_eq!(
+            menu.handle(Input::Select),
+            Some(MenuEvent::Changed { id: BEEP, value: 0 })
+        );
+        assert!(!menu.is_editing());
+
+        menu.handle(Input::Rotate(2));
+        assert_eq!(
+            menu.handle(Input::Select),
+            Some(MenuEvent::Action { id: RESET })
+        );
+    }
+
+    #[test]
+    fn enters_and_leaves_submenus() {
+        let mut menu = menu();
+        assert_eq!(menu.title(), "Settings");
+
+        menu.handle(Input::Rotate(2));
+        menu.handle(Input::Select);
+        assert_eq!(menu.depth(), 1);
+        assert_eq!(menu.title(), "Display");
+        assert_eq!(menu.items(), DISPLAY);
+        assert_eq!(menu.cursor(), 0);
+
+        // choices wrap around their options
+        menu.handle(Input::Select);
+        assert_eq!(
+            menu.handle(Input::Rotate(-1)),
+            Some(MenuEvent::Changed { id: UNIT, value: 2 })
+        );
+        menu.handle(Input::Back);
+        assert!(!menu.is_editing());
+
+        // back to the cursor left in the parent
+        assert_eq!(menu.handle(Input::Back), None);
+        assert_eq!(menu.depth(), 0);
+        assert_eq!(menu.title(), "Settings");
+        assert_eq!(menu.cursor(), 2);
+
+        assert_eq!(menu.handle(Input::Back), Some(MenuEvent::Exited));
+    }
+
+    #[test]
+    fn stops_at_the_deepest_level() {
+        const INNER: &[Item] = &[Item::Toggle {
+            label: "Inner",
+            id: 0,
+        }];
+        const OUTER: &[Item] = &[Item::Submenu {
+            label: "Outer",
+            items: INNER,
+        }];
+        const NESTED: &[Item] = &[Item::Submenu {
+            label: "Nested",
+            items: OUTER,
+        }];
+        let mut menu: Menu<1, 2> = Menu::new("Root", NESTED);
+
+        menu.handle(Input::Select);
+        menu.handle(Input::Select);
+        assert_eq!(menu.depth(), 1);
+        assert_eq!(menu.title(), "Nested");
+    }
+
+    #[test]
+    fn a_click_selects_and_a_long_press_goes_back() {
+        let mut menu = menu();
+        menu.handle(Input::Rotate(2));
+
+        assert_eq!(menu.handle_button(ButtonEvent::Pressed), None);
+        assert_eq!(menu.handle_button(ButtonEvent::Released), None);
+        assert_eq!(menu.depth(), 1);
+
+        // the release ending a long press isn't a click
+        menu.handle_button(ButtonEvent::Pressed);
+        menu.handle_button(ButtonEvent::LongPress(800));
+        assert_eq!(menu.depth(), 0);
+        assert_eq!(menu.handle_button(ButtonEvent::Released), None);
+        assert_eq!(menu.depth(), 0);
+
+        assert_eq!(menu.handle_button(ButtonEvent::DoubleClick), None);
+        assert_eq!(menu.depth(), 0);
+    }
+}
@@ -0,0 +1,139 @@
+//! Drawing of a [`Menu`] on a monochrome display.
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+
+use super::{Item, Menu};
+
+const ROW_HEIGHT: u32 = 10;
+// title row and the line under it
+const HEADER_HEIGHT: u32 = ROW_HEIGHT + 3;
+
+/// Draws the open menu of `menu`: its title, then as many items as fit with
+/// the selected one highlighted and the value being edited between angle
+/// brackets.
+pub fn render<D, const V: usize, const DEPTH: usize>(
+    menu: &Menu<'_, V, DEPTH>,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let area = target.bounding_box();
+    let width = area.size.width as i32;
+    let on = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let off = MonoTextStyle::new(&FONT_6X10, BinaryColor::Off);
+
+    target.clear(BinaryColor::Off)?;
+
+    Text::with_baseline(menu.title(), area.top_left, on, Baseline::Top).draw(target)?;
+    Line::new(
+        area.top_left + Point::new(0, ROW_HEIGHT as i32 + 1),
+        area.top_left + Point::new(width - 1, ROW_HEIGHT as i32 + 1),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+    .draw(target)?;
+
+    // scroll so the cursor stays visible
+    let rows = (area.size.height.saturating_sub(HEADER_HEIGHT) / ROW_HEIGHT).max(1) as usize;
+    let first = (menu.cursor() + 1).saturating_sub(rows);
+
+    for (row, item) in menu.items().iter().skip(first).take(rows).enumerate() {
+        let top = area.top_left + Point::new(0, (HEADER_HEIGHT + row as u32 * ROW_HEIGHT) as i32);
+        let selected = first + row == menu.cursor();
+
+        let style = if selected {
+            Rectangle::new(top, Size::new(area.size.width, ROW_HEIGHT))
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(target)?;
+            off
+        } else {
+            on
+        };
+
+        Text::with_baseline(item.label(), top + Point::new(1, 0), style, Baseline::Top)
+            .draw(target)?;
+
+        let mut value = TextBuffer::new();
+        write_value(&mut value, menu, item, selected && menu.is_editing()).ok();
+        Text::with_text_style(
+            value.as_str(),
+            top + Point::new(width - 2, 0),
+            style,
+            TextStyleBuilder::new()
+                .alignment(Alignment::Right)
+                .baseline(Baseline::Top)
+                .build(),
+        )
+        .draw(target)?;
+    }
+
+    Ok(())
+}
+
+fn write_value<const V: usize, const DEPTH: usize>(
+    out: &mut TextBuffer,
+    menu: &Menu<'_, V, DEPTH>,
+    item: &Item<'_>,
+    editing: bool,
+) -> fmt::Result {
+    if editing {
+        out.write_char('<')?;
+    }
+
+    match *item {
+        Item::Submenu { .. } => out.write_char('>')?,
+        Item::Number { id, .. } => write!(out, "{}", menu.value(id))?,
+        Item::Toggle { id, .. } => out.write_str(if menu.value(id) != 0 { "on" } else { "off" })?,
+        Item::Choice { id, options, .. } => {
+            let option = options.get(menu.value(id) as usize).copied().unwrap_or("?");
+            out.write_str(option)?
+        }
+        Item::Action { .. } => (),
+    }
+
+    if editing {
+        out.write_char('>')?;
+    }
+    Ok(())
+}
+
+/// Small stack buffer to format values into.
+struct TextBuffer {
+    bytes: [u8; 24],
+    len: usize,
+}
+
+impl TextBuffer {
+    fn new() -> Self {
+        Self {
+            bytes: [0; 24],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // only whole `str`s are written
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+impl Write for TextBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.bytes.len() {
+            return Err(fmt::Error);
+        }
+
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
@@ -0,0 +1,286 @@
+//! Test doubles of the `embedded-hal` traits, recording what the drivers do.
+
+extern crate std;
+
+use core::{
+    cell::{Cell, RefCell},
+    convert::Infallible,
+};
+use std::vec::Vec;
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{self, InputPin, OutputPin},
+    pwm::{self, SetDutyCycle},
+    spi::{self, Operation, SpiDevice},
+};
+
+use crate::{seven_segment::SegmentDisplay, sonar};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Level set on the named pin.
+    Pin(&'static str, bool),
+    /// Mask written to a [`Segments`].
+    Mask(u8),
+    /// Duty cycle set on the named [`Pwm`], out of its maximum.
+    Duty(&'static str, u16),
+    /// Bytes written in one transaction of a [`Spi`].
+    Spi(Vec<u8>),
+}
+
+/// Events of the doubles sharing it, in order.
+#[derive(Default)]
+pub struct Log(RefCell<Vec<Event>>);
+
+impl Log {
+    pub fn pin(&self, name: &'static str) -> Pin<'_> {
+        Pin { name, log: self }
+    }
+
+    /// Open-drain pin pulled low by the device it talks to while `acking`.
+    pub fn open_drain(&self, name: &'static str, acking: bool) -> OpenDrain<'_> {
+        OpenDrain {
+            pin: self.pin(name),
+            acking,
+            high: true,
+        }
+    }
+
+    /// PWM channel whose duty cycle goes from `0` to `max_duty`.
+    pub fn pwm(&self, name: &'static str, max_duty: u16) -> Pwm<'_> {
+        Pwm {
+            name,
+            log: self,
+            max_duty,
+        }
+    }
+
+    pub fn spi(&self) -> Spi<'_> {
+        Spi { log: self }
+    }
+
+    pub fn segments(&self) -> Segments<'_> {
+        Segments { log: self, mask: 0 }
+    }
+
+    pub fn push(&self, event: Event) {
+        self.0.borrow_mut().push(event);
+    }
+
+    /// Events since the last call.
+    pub fn take(&self) -> Vec<Event> {
+        self.0.take()
+    }
+
+    /// Levels set on the pin `name` since the last call, forgetting the
+    /// other events.
+    pub fn levels(&self, name: &str) -> Vec<bool> {
+        self.take()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Pin(pin, high) if pin == name => Some(high),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+pub struct Pin<'a> {
+    name: &'static str,
+    log: &'a Log,
+}
+
+impl digital::ErrorType for Pin<'_> {
+    type Error = Infallible;
+}
+
+impl OutputPin for Pin<'_> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.log.push(Event::Pin(self.name, false));
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.log.push(Event::Pin(self.name, true));
+        Ok(())
+    }
+}
+
+/// Pin whose level is read back, unless the device pulls it low.
+pub struct OpenDrain<'a> {
+    pin: Pin<'a>,
+    acking: bool,
+    high: bool,
+}
+
+impl digital::ErrorType for OpenDrain<'_> {
+    type Error = Infallible;
+}
+
+impl OutputPin for OpenDrain<'_> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.high = false;
+        self.pin.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.high = true;
+        self.pin.set_high()
+    }
+}
+
+impl InputPin for OpenDrain<'_> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.high && !self.acking)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+pub struct Pwm<'a> {
+    name: &'static str,
+    log: &'a Log,
+    max_duty: u16,
+}
+
+impl pwm::ErrorType for Pwm<'_> {
+    type Error = Infallible;
+}
+
+impl SetDutyCycle for Pwm<'_> {
+    fn max_duty_cycle(&self) -> u16 {
+        self.max_duty
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        self.log.push(Event::Duty(self.name, duty));
+        Ok(())
+    }
+}
+
+/// SPI device recording what is written to it.
+pub struct Spi<'a> {
+    log: &'a Log,
+}
+
+impl spi::ErrorType for Spi<'_> {
+    type Error = Infallible;
+}
+
+impl SpiDevice for Spi<'_> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        let mut bytes = Vec::new();
+        for operation in operations {
+            match operation {
+                Operation::Write(words) => bytes.extend_from_slice(words),
+                _ => panic!("only writes are recorded"),
+            }
+        }
+
+        self.log.push(Event::Spi(bytes));
+        Ok(())
+    }
+}
+
+/// Segment lines recorded as whole masks.
+pub struct Segments<'a> {
+    log: &'a Log,
+    mask: u8,
+}
+
+impl SegmentDisplay for Segments<'_> {
+    type Error = Infallible;
+
+    fn write_mask(&mut self, mask: u8) -> Result<(), Self::Error> {
+        self.log.push(Event::Mask(mask));
+        self.mask = mask;
+        Ok(())
+    }
+
+    fn mask(&self) -> u8 {
+        self.mask
+    }
+}
+
+/// Time of the doubles sharing it, in microseconds.
+#[derive(Default)]
+pub struct Time(Cell<u64>);
+
+impl Time {
+    pub fn clock(&self) -> Clock<'_> {
+        Clock { time: self }
+    }
+
+    pub fn delay(&self) -> Delay<'_> {
+        Delay { time: self }
+    }
+
+    /// Echo line high from the rise to the fall of each of `pulses`.
+    pub fn echo<'a>(&'a self, pulses: &'a [(u64, u64)]) -> Echo<'a> {
+        Echo { time: self, pulses }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.0.get()
+    }
+
+    pub fn advance(&self, us: u64) {
+        self.0.set(self.0.get() + us);
+    }
+}
+
+/// Clock of microsecond ticks, one going by on each reading like a timer
+/// polled in a loop.
+pub struct Clock<'a> {
+    time: &'a Time,
+}
+
+impl sonar::Clock for Clock<'_> {
+    fn now(&self) -> u64 {
+        let now = self.time.now();
+        self.time.advance(1);
+        now
+    }
+
+    fn ticks_per_second(&self) -> u32 {
+        1_000_000
+    }
+}
+
+/// Delay letting the time go by, rounded up to the microsecond.
+pub struct Delay<'a> {
+    time: &'a Time,
+}
+
+impl DelayNs for Delay<'_> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.time.advance(ns.div_ceil(1000) as u64);
+    }
+}
+
+/// Input pin high during scripted pulses.
+pub struct Echo<'a> {
+    time: &'a Time,
+    pulses: &'a [(u64, u64)],
+}
+
+impl digital::ErrorType for Echo<'_> {
+    type Error = Infallible;
+}
+
+impl InputPin for Echo<'_> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        let now = self.time.now();
+        Ok(self
+            .pulses
+            .iter()
+            .any(|&(rise, fall)| (rise..fall).contains(&now)))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
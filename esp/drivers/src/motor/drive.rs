@@ -0,0 +1,328 @@
+//! Differential drive: two wheels steered by their speed difference.
+
+use core::str::FromStr;
+
+use super::MAX_SPEED;
+
+/// What the pilot asks for, each value from `-100` to `100`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DriveInput {
+    /// Forward speed and turn rate, positive turning right.
+    Arcade { throttle: i8, steer: i8 },
+    /// Speed of each side.
+    Tank { left: i8, right: i8 },
+}
+
+impl DriveInput {
+    pub const STOP: Self = DriveInput::Tank { left: 0, right: 0 };
+
+    /// Tank input from a button per side, e.g. the two buttons of the dual
+    /// motor rig: one button turns, both drive straight.
+    pub fn from_buttons(left: bool, right: bool, speed: i8) -> Self {
+        let speed = speed.clamp(0, MAX_SPEED);
+        DriveInput::Tank {
+            left: if left { speed } else { 0 },
+            right: if right { speed } else { 0 },
+        }
+    }
+}
+
+/// Error parsing a [`DriveInput`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseDriveError {
+    /// Not `ARCADE` nor `TANK`.
+    UnknownMode,
+    /// Missing, extra or non-numeric value.
+    InvalidValue,
+    /// Value outside of `-100..=100`.
+    OutOfRange,
+}
+
+/// Parses `ARCADE <throttle> <steer>` or `TANK <left> <right>`, the mode
+/// being case insensitive, as sent over a serial link.
+impl FromStr for DriveInput {
+    type Err = ParseDriveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_ascii_whitespace();
+        let mode = words.next().ok_or(ParseDriveError::UnknownMode)?;
+        let tank = if mode.eq_ignore_ascii_case("TANK") {
+            true
+        } else if mode.eq_ignore_ascii_case("ARCADE") {
+            false
+        } else {
+            return Err(ParseDriveError::UnknownMode);
+        };
+
+        let first = parse_speed(words.next())?;
+        let second = parse_speed(words.next())?;
+        if words.next().is_some() {
+            return Err(ParseDriveError::InvalidValue);
+        }
+
+        Ok(if tank {
+            DriveInput::Tank {
+                left: first,
+                right: second,
+            }
+        } else {
+            DriveInput::Arcade {
+                throttle: first,
+                steer: second,
+            }
+        })
+    }
+}
+
+/// Parses a speed from `-100` to `100`.
+pub(crate) fn parse_speed(word: Option<&str>) -> Result<i8, ParseDriveError> {
+    let value: i16 = word
+        .and_then(|word| word.parse().ok())
+        .ok_or(ParseDriveError::InvalidValue)?;
+    if value.unsigned_abs() > MAX_SPEED as u16 {
+        return Err(ParseDriveError::OutOfRange);
+    }
+
+    Ok(value as i8)
+}
+
+/// Axis value from a button pushing it each way, `0` when both or none are
+/// pressed.
+pub fn axis_from_buttons(negative: bool, positive: bool, speed: i8) -> i8 {
+    let speed = speed.clamp(0, MAX_SPEED);
+    match (negative, positive) {
+        (true, false) => -speed,
+        (false, true) => speed,
+        _ => 0,
+    }
+}
+
+/// Axis read from a potentiometer or joystick through an ADC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AdcAxis {
+    min: u16,
+    center: u16,
+    max: u16,
+}
+
+impl AdcAxis {
+    /// Axis reading `-100` at `min`, `0` at `center` and `100` at `max`.
+    ///
+    /// # Panics
+    ///
+    /// If `center` is not strictly between `min` and `max`.
+    pub const fn new(min: u16, center: u16, max: u16) -> Self {
+        assert!(min < center && center < max, "axis center out of its range");
+
+        Self { min, center, max }
+    }
+
+    /// Axis centered in the middle of `min..=max`.
+    pub const fn centered(min: u16, max: u16) -> Self {
+        Self::new(min, min + (max - min) / 2, max)
+    }
+
+    pub fn value(&self, reading: u16) -> i8 {
+        let reading = reading.clamp(self.min, self.max) as i32;
+        let center = self.center as i32;
+        let value = if reading >= center {
+            (reading - center) * MAX_SPEED as i32 / (self.max as i32 - center)
+        } else {
+            (reading - center) * MAX_SPEED as i32 / (center - self.min as i32)
+        };
+
+        value as i8
+    }
+}
+
+/// Speed of each wheel, from `-100` to `100`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Wheels {
+    pub left: i8,
+    pub right: i8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DriveConfig {
+    /// Inputs within this distance of `0` are `0`, so that a joystick at rest
+    /// doesn't creep. The rest of the range is stretched back to `0..=100`.
+    pub deadband: u8,
+    /// Slows down the right wheel when positive, the left one when negative,
+    /// by this percentage, to make up for mismatched motors.
+    pub trim: i8,
+    /// Whether a wheel's motor is mounted the other way round.
+    pub invert_left: bool,
+    pub invert_right: bool,
+}
+
+impl DriveConfig {
+    pub const DEFAULT: Self = Self {
+        deadband: 5,
+        trim: 0,
+        invert_left: false,
+        invert_right: false,
+    };
+}
+
+impl Default for DriveConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Mixes [`DriveInput`]s into the speed of each wheel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DifferentialDrive {
+    config: DriveConfig,
+}
+
+impl DifferentialDrive {
+    pub const fn new(config: DriveConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &DriveConfig {
+        &self.config
+    }
+
+    pub fn set_trim(&mut self, trim: i8) {
+        self.config.trim = trim.clamp(-MAX_SPEED, MAX_SPEED);
+    }
+
+    pub fn mix(&self, input: DriveInput) -> Wheels {
+        let (left, right) = match input {
+            DriveInput::Arcade { throttle, steer } => {
+                let throttle = self.deadband(throttle);
+                let steer = self.deadband(steer);
+                (throttle + steer, throttle - steer)
+            }
+            DriveInput::Tank { left, right } => (self.deadband(left), self.deadband(right)),
+        };
+
+        // scales both sides down together when one is past full speed, so
+        // that turning at full throttle keeps the ratio between them
+        let max = MAX_SPEED as i32;
+        let largest = left.abs().max(right.abs());
+        let (mut left, mut right) = if largest > max {
+            (left * max / largest, right * max / largest)
+        } else {
+            (left, right)
+        };
+
+        let trim = (self.config.trim as i32).clamp(-max, max);
+        if trim > 0 {
+            right = right * (max - trim) / max;
+        } else {
+            left = left * (max + trim) / max;
+        }
+
+        if self.config.invert_left {
+            left = -left;
+        }
+        if self.config.invert_right {
+            right = -right;
+        }
+
+        Wheels {
+            left: left as i8,
+            right: right as i8,
+        }
+    }
+
+    fn deadband(&self, value: i8) -> i32 {
+        let max = MAX_SPEED as i32;
+        let deadband = (self.config.deadband as i32).min(max - 1);
+        let value = (value as i32).clamp(-max, max);
+        if value.abs() <= deadband {
+            return 0;
+        }
+
+        value.signum() * (value.abs() - deadband) * max / (max - deadband)
+    }
+}
+
+impl Default for DifferentialDrive {
+    fn default() -> Self {
+        Self::new(DriveConfig::DEFAULT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_drive_inputs() {
+        assert_eq!(
+            "tank -40 100".parse(),
+            Ok(DriveInput::Tank {
+                left: -40,
+                right: 100
+            })
+        );
+        assert_eq!(
+            "ARCADE 0 -100".parse(),
+            Ok(DriveInput::Arcade {
+                throttle: 0,
+                steer: -100
+            })
+        );
+        assert_eq!(
+            "SPIN 1 2".parse::<DriveInput>(),
+            Err(ParseDriveError::UnknownMode)
+        );
+        assert_eq!(
+            "TANK 1".parse::<DriveInput>(),
+            Err(ParseDriveError::InvalidValue)
+        );
+        assert_eq!(
+            "TANK 1 2 3".parse::<DriveInput>(),
+            Err(ParseDriveError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn rejects_speeds_out_of_range() {
+        assert_eq!(parse_speed(Some("-100")), Ok(-100));
+        assert_eq!(parse_speed(Some("101")), Err(ParseDriveError::OutOfRange));
+        assert_eq!(parse_speed(Some("-101")), Err(ParseDriveError::OutOfRange));
+        // no absolute value in an i16
+        assert_eq!(
+            parse_speed(Some("-32768")),
+            Err(ParseDriveError::OutOfRange)
+        );
+        assert_eq!(
+            parse_speed(Some("32768")),
+            Err(ParseDriveError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn mixes_arcade_inputs_keeping_the_ratio() {
+        let drive = DifferentialDrive::new(DriveConfig {
+            deadband: 0,
+            ..DriveConfig::DEFAULT
+        });
+
+        assert_eq!(
+            drive.mix(DriveInput::Arcade {
+                throttle: 50,
+                steer: 20
+            }),
+            Wheels {
+                left: 70,
+                right: 30
+            }
+        );
+        assert_eq!(
+            drive.mix(DriveInput::Arcade {
+                throttle: 100,
+                steer: 50
+            }),
+            Wheels {
+                left: 100,
+                right: 33
+            }
+        );
+    }
+}
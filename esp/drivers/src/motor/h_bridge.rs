@@ -0,0 +1,259 @@
+//! Two-input H-bridge (L298N, L9110, DRV8833, TB6612...) driven by PWM.
+
+use embedded_hal::pwm::SetDutyCycle;
+
+use super::MAX_SPEED;
+
+/// What the bridge does with the motor.
+///
+/// The inputs are only ever written from one of these states, so the
+/// forward and reverse inputs can't both be driven with a duty cycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MotorState {
+    /// Both inputs low, the motor spins freely.
+    Coast,
+    /// Both inputs high, the bridge shorts the motor to stop it quickly.
+    Brake,
+    /// Forward input at this duty cycle in percent, reverse input low.
+    Forward(u8),
+    /// Reverse input at this duty cycle in percent, forward input low.
+    Reverse(u8),
+}
+
+/// Motor on an H-bridge with one PWM channel per input.
+pub struct HBridge<A, B> {
+    forward: A,
+    reverse: B,
+    state: MotorState,
+}
+
+impl<A, B, E> HBridge<A, B>
+where
+    A: SetDutyCycle<Error = E>,
+    B: SetDutyCycle<Error = E>,
+{
+    /// Takes the PWM channels of the two inputs and lets the motor coast.
+    pub fn new(forward: A, reverse: B) -> Result<Self, E> {
+        let mut motor = Self {
+            forward,
+            reverse,
+            state: MotorState::Coast,
+        };
+        motor.forward.set_duty_cycle_fully_off()?;
+        motor.reverse.set_duty_cycle_fully_off()?;
+
+        Ok(motor)
+    }
+
+    /// Sets the speed from `-100` (full reverse) to `100` (full forward), `0`
+    /// letting the motor coast.
+    pub fn set_speed(&mut self, speed: i8) -> Result<(), E> {
+        let speed = speed.clamp(-MAX_SPEED, MAX_SPEED);
+        let state = match speed {
+            0 => MotorState::Coast,
+            1.. => MotorState::Forward(speed as u8),
+            _ => MotorState::Reverse(speed.unsigned_abs()),
+        };
+
+        self.set_state(state)
+    }
+
+    pub fn brake(&mut self) -> Result<(), E> {
+        self.set_state(MotorState::Brake)
+    }
+
+    pub fn coast(&mut self) -> Result<(), E> {
+        self.set_state(MotorState::Coast)
+    }
+
+    pub fn set_state(&mut self, state: MotorState) -> Result<(), E> {
+        let state = match state {
+            MotorState::Forward(duty) => MotorState::Forward(duty.min(MAX_SPEED as u8)),
+            MotorState::Reverse(duty) => MotorState::Reverse(duty.min(MAX_SPEED as u8)),
+            state => state,
+        };
+
+        // the input going low is always written first so that the motor
+        // coasts in between two states rather than being driven both ways
+        match state {
+            MotorState::Coast => {
+                self.forward.set_duty_cycle_fully_off()?;
+                self.reverse.set_duty_cycle_fully_off()?;
+            }
+            MotorState::Brake => {
+                self.forward.set_duty_cycle_fully_off()?;
+                self.reverse.set_duty_cycle_fully_off()?;
+                self.forward.set_duty_cycle_fully_on()?;
+                self.reverse.set_duty_cycle_fully_on()?;
+            }
+            MotorState::Forward(duty) => {
+                self.reverse.set_duty_cycle_fully_off()?;
+                self.forward.set_duty_cycle_percent(duty)?;
+            }
+            MotorState::Reverse(duty) => {
+                self.forward.set_duty_cycle_fully_off()?;
+                self.reverse.set_duty_cycle_percent(duty)?;
+            }
+        }
+
+        self.state = state;
+        Ok(())
+    }
+
+    pub fn state(&self) -> MotorState {
+        self.state
+    }
+
+    /// Current speed, `0` when coasting or braking.
+    pub fn speed(&self) -> i8 {
+        match self.state {
+            MotorState::Coast | MotorState::Brake => 0,
+            MotorState::Forward(duty) => duty as i8,
+            MotorState::Reverse(duty) => -(duty as i8),
+        }
+    }
+
+    /// Gives the PWM channels back, letting the motor coast first.
+    pub fn release(mut self) -> Result<(A, B), E> {
+        self.coast()?;
+        Ok((self.forward, self.reverse))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::mock::{Event, Log, Pwm};
+
+    const STATES: [MotorState; 5] = [
+        MotorState::Coast,
+        MotorState::Brake,
+        MotorState::Forward(40),
+        MotorState::Forward(100),
+        MotorState::Reverse(70),
+    ];
+
+    fn bridge(log: &Log) -> HBridge<Pwm<'_>, Pwm<'_>> {
+        HBridge::new(log.pwm("fwd", 100), log.pwm("rev", 100)).unwrap()
+    }
+
+    // duty cycles of both inputs after each write, from `start`
+    fn duties(start: (u16, u16), events: Vec<Event>) -> Vec<(u16, u16)> {
+        let (mut forward, mut reverse) = start;
+        events
+            .into_iter()
+            .map(|event| {
+                match event {
+                    Event::Duty("fwd", duty) => forward = duty,
+                    Event::Duty("rev", duty) => reverse = duty,
+                    event => panic!("unexpected {event:?}"),
+                }
+                (forward, reverse)
+            })
+            .collect()
+    }
+
+    fn steady(state: MotorState) -> (u16, u16) {
+        match state {
+            MotorState::Coast => (0, 0),
+            MotorState::Brake => (100, 100),
+            MotorState::Forward(duty) => (duty as u16, 0),
+            MotorState::Reverse(duty) => (0, duty as u16),
+        }
+    }
+
+    #[test]
+    fn starts_coasting() {
+        let log = Log::default();
+        let motor = bridge(&log);
+
+        assert_eq!(motor.state(), MotorState::Coast);
+        assert_eq!(log.take(), [Event::Duty("fwd", 0), Event::Duty("rev", 0)]);
+    }
+
+    #[test]
+    fn never_drives_both_ways() {
+        for from in STATES {
+            for to in STATES {
+                let log = Log::default();
+                let mut motor = bridge(&log);
+                motor.set_state(from).unwrap();
+                log.take();
+
+                motor.set_state(to).unwrap();
+                let duties = duties(steady(from), log.take());
+
+                // both inputs on only when both fully on, braking
+                for &(forward, reverse) in &duties {
+                    assert!(
+                        forward == 0 || reverse == 0 || (forward, reverse) == (100, 100),
+                        "{from:?} to {to:?} went through {forward}/{reverse}"
+                    );
+                }
+                // the input going off first
+                if let (MotorState::Forward(_), MotorState::Reverse(_))
+                | (MotorState::Reverse(_), MotorState::Forward(_)) = (from, to)
+                {
+                    assert_eq!(duties[0], (0, 0), "{from:?} to {to:?}");
+                }
+                assert_eq!(duties.last(), Some(&steady(to)));
+                assert_eq!(motor.state(), to);
+            }
+        }
+    }
+
+    #[test]
+    fn switches_the_off_input_first() {
+        let log = Log::default();
+        let mut motor = bridge(&log);
+        log.take();
+
+        motor.set_speed(60).unwrap();
+        assert_eq!(log.take(), [Event::Duty("rev", 0), Event::Duty("fwd", 60)]);
+        motor.set_speed(-30).unwrap();
+        assert_eq!(log.take(), [Event::Duty("fwd", 0), Event::Duty("rev", 30)]);
+        motor.brake().unwrap();
+        assert_eq!(
+            log.take(),
+            [
+                Event::Duty("fwd", 0),
+                Event::Duty("rev", 0),
+                Event::Duty("fwd", 100),
+                Event::Duty("rev", 100)
+            ]
+        );
+    }
+
+    #[test]
+    fn clamps_the_speed() {
+        let log = Log::default();
+        let mut motor = bridge(&log);
+
+        motor.set_speed(i8::MIN).unwrap();
+        assert_eq!(motor.state(), MotorState::Reverse(100));
+        assert_eq!(motor.speed(), -100);
+        motor.set_state(MotorState::Forward(250)).unwrap();
+        assert_eq!(motor.state(), MotorState::Forward(100));
+        assert_eq!(log.take().last(), Some(&Event::Duty("fwd", 100)));
+
+        motor.set_speed(0).unwrap();
+        assert_eq!(motor.state(), MotorState::Coast);
+        motor.brake().unwrap();
+        assert_eq!(motor.speed(), 0);
+    }
+
+    #[test]
+    fn coasts_when_released() {
+        let log = Log::default();
+        let mut motor = bridge(&log);
+        motor.set_speed(50).unwrap();
+        log.take();
+
+        motor.release().unwrap();
+        assert_eq!(log.take(), [Event::Duty("fwd", 0), Event::Duty("rev", 0)]);
+    }
+}
@@ -0,0 +1,21 @@
+//! DC motors behind an H-bridge.
+
+mod drive;
+mod h_bridge;
+#[cfg(test)]
+mod model;
+mod pid;
+mod ramp;
+mod speed;
+
+pub(crate) use drive::parse_speed;
+pub use drive::{
+    axis_from_buttons, AdcAxis, DifferentialDrive, DriveConfig, DriveInput, ParseDriveError, Wheels,
+};
+pub use h_bridge::{HBridge, MotorState};
+pub use pid::{Pid, PidConfig};
+pub use ramp::{Ramp, RampConfig};
+pub use speed::{SpeedController, Tachometer};
+
+/// Highest speed, in percent of the supply voltage.
+pub const MAX_SPEED: i8 = 100;
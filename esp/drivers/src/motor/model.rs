@@ -0,0 +1,74 @@
+//! First-order DC motor model, to tune speed control on the host.
+
+use super::MAX_SPEED;
+
+/// Motor whose speed follows its PWM speed with a lag, like a DC motor and
+/// its load, turning an encoder.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FirstOrderMotor {
+    max_rpm: f32,
+    time_constant_ms: f32,
+    counts_per_revolution: u32,
+    rpm: f32,
+    revolutions: f32,
+}
+
+impl FirstOrderMotor {
+    /// Motor turning at `max_rpm` at full speed, reaching 63% of a new speed
+    /// after `time_constant_ms`.
+    pub const fn new(max_rpm: f32, time_constant_ms: f32, counts_per_revolution: u32) -> Self {
+        Self {
+            max_rpm,
+            time_constant_ms,
+            counts_per_revolution,
+            rpm: 0.0,
+            revolutions: 0.0,
+        }
+    }
+
+    /// Runs the motor at `speed`, from `-100` to `100`, for `elapsed_ms`.
+    pub fn step(&mut self, speed: i8, elapsed_ms: u32) {
+        let steady = speed.clamp(-MAX_SPEED, MAX_SPEED) as f32 / MAX_SPEED as f32 * self.max_rpm;
+        let elapsed_ms = elapsed_ms as f32;
+
+        // backward Euler, stable whatever the step
+        let alpha = elapsed_ms / (self.time_constant_ms + elapsed_ms);
+        self.rpm += (steady - self.rpm) * alpha;
+        self.revolutions += self.rpm * elapsed_ms / 60_000.0;
+    }
+
+    pub fn rpm(&self) -> f32 {
+        self.rpm
+    }
+
+    /// Encoder position, in counts.
+    pub fn position(&self) -> i32 {
+        (self.revolutions * self.counts_per_revolution as f32) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reaches_63_percent_after_the_time_constant() {
+        let mut motor = FirstOrderMotor::new(200.0, 100.0, 840);
+        for _ in 0..100 {
+            motor.step(50, 1);
+        }
+
+        assert!((motor.rpm() - 63.2).abs() < 1.0, "{}", motor.rpm());
+    }
+
+    #[test]
+    fn counts_the_revolutions() {
+        let mut motor = FirstOrderMotor::new(200.0, 100.0, 840);
+        for _ in 0..1000 {
+            motor.step(-100, 10);
+        }
+
+        // 200 rpm backwards for most of 10 s
+        assert!((-28_000..-27_500).contains(&motor.position()));
+    }
+}
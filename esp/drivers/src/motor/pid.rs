@@ -0,0 +1,136 @@
+//! PID controller with a clamped output and anti-windup.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PidConfig {
+    pub kp: f32,
+    /// Integral gain, per second.
+    pub ki: f32,
+    /// Derivative gain, in seconds.
+    pub kd: f32,
+    pub output_min: f32,
+    pub output_max: f32,
+}
+
+impl PidConfig {
+    /// Proportional only, output from `-100` to `100` like a motor speed.
+    pub const DEFAULT: Self = Self {
+        kp: 1.0,
+        ki: 0.0,
+        kd: 0.0,
+        output_min: -100.0,
+        output_max: 100.0,
+    };
+
+    pub const fn with_gains(self, kp: f32, ki: f32, kd: f32) -> Self {
+        Self { kp, ki, kd, ..self }
+    }
+
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`.
+    pub fn with_output_limits(self, min: f32, max: f32) -> Self {
+        assert!(min <= max, "output lower limit above its upper limit");
+
+        Self {
+            output_min: min,
+            output_max: max,
+            ..self
+        }
+    }
+}
+
+impl Default for PidConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Drives a measured value towards a setpoint.
+///
+/// The derivative term acts on the measurement rather than the error so that
+/// a setpoint change doesn't kick the output. The integral stops growing while
+/// the output is clamped and pushed further the same way, so it doesn't wind
+/// up while the motor can't keep up and overshoot once it does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pid {
+    config: PidConfig,
+    integral: f32,
+    previous: Option<f32>,
+    output: f32,
+}
+
+impl Pid {
+    pub const fn new(config: PidConfig) -> Self {
+        Self {
+            config,
+            integral: 0.0,
+            previous: None,
+            output: 0.0,
+        }
+    }
+
+    pub fn config(&self) -> &PidConfig {
+        &self.config
+    }
+
+    /// Changes the gains and limits on the fly, keeping the integral term
+    /// within the new limits.
+    pub fn set_config(&mut self, config: PidConfig) {
+        self.config = config;
+        self.integral = self.integral.clamp(config.output_min, config.output_max);
+    }
+
+    /// Forgets the history, e.g. when the motor was stopped by other means.
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.previous = None;
+        self.output = 0.0;
+    }
+
+    /// Last output.
+    pub fn output(&self) -> f32 {
+        self.output
+    }
+
+    /// Computes the output from the measurement taken `dt` seconds after the
+    /// previous one.
+    pub fn update(&mut self, setpoint: f32, measured: f32, dt: f32) -> f32 {
+        let PidConfig {
+            kp,
+            ki,
+            kd,
+            output_min,
+            output_max,
+        } = self.config;
+
+        let error = setpoint - measured;
+        let derivative = match self.previous {
+            Some(previous) if dt > 0.0 => -(measured - previous) / dt,
+            _ => 0.0,
+        };
+        self.previous = Some(measured);
+
+        let proportional = kp * error;
+        let differential = kd * derivative;
+        let integral = (self.integral + ki * error * dt).clamp(output_min, output_max);
+
+        let unclamped = proportional + integral + differential;
+        let output = unclamped.clamp(output_min, output_max);
+
+        // integrates only when it doesn't push further into the limit
+        let saturated =
+            (unclamped > output_max && error > 0.0) || (unclamped < output_min && error < 0.0);
+        if !saturated {
+            self.integral = integral;
+        }
+
+        self.output = output;
+        output
+    }
+}
+
+impl Default for Pid {
+    fn default() -> Self {
+        Self::new(PidConfig::DEFAULT)
+    }
+}
@@ -0,0 +1,214 @@
+//! Soft start and stop: speed changes limited in percent per second.
+
+use super::MAX_SPEED;
+
+// speeds are kept in thousandths of a percent, so that a rate in %/s times
+// milliseconds gives the change
+const SCALE: i32 = 1000;
+// longest time accounted for in one update, keeping the products in range
+const MAX_ELAPSED_MS: u32 = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RampConfig {
+    /// Fastest speed-up away from a standstill in %/s, `0` for no limit.
+    pub acceleration: u16,
+    /// Fastest slow-down towards a standstill in %/s, `0` for no limit.
+    pub deceleration: u16,
+}
+
+impl RampConfig {
+    /// From a standstill to full speed in half a second, back in a quarter.
+    pub const DEFAULT: Self = Self {
+        acceleration: 200,
+        deceleration: 400,
+    };
+}
+
+impl Default for RampConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Speed going to a target at a limited rate.
+///
+/// [`Ramp::update`] is meant to be called from a periodic timer with the time
+/// elapsed since its last call, its result written to the motor. Reversing
+/// slows down to a standstill at the deceleration rate first, then speeds up
+/// the other way at the acceleration rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ramp {
+    config: RampConfig,
+    target: i8,
+    speed: i32,
+}
+
+impl Ramp {
+    pub const fn new(config: RampConfig) -> Self {
+        Self {
+            config,
+            target: 0,
+            speed: 0,
+        }
+    }
+
+    pub fn set_config(&mut self, config: RampConfig) {
+        self.config = config;
+    }
+
+    /// Speed to reach, from `-100` to `100`.
+    pub fn set_target(&mut self, target: i8) {
+        self.target = target.clamp(-MAX_SPEED, MAX_SPEED);
+    }
+
+    pub fn target(&self) -> i8 {
+        self.target
+    }
+
+    /// Current speed, rounded towards `0`.
+    pub fn speed(&self) -> i8 {
+        (self.speed / SCALE) as i8
+    }
+
+    /// Whether the speed reached the target.
+    pub fn is_settled(&self) -> bool {
+        self.speed == self.target as i32 * SCALE
+    }
+
+    /// Stops right away without ramping down, e.g. before braking.
+    pub fn emergency_stop(&mut self) {
+        self.target = 0;
+        self.speed = 0;
+    }
+
+    /// Moves the speed towards the target as `elapsed_ms` went by and returns
+    /// it.
+    pub fn update(&mut self, elapsed_ms: u32) -> i8 {
+        let target = self.target as i32 * SCALE;
+        let mut elapsed = elapsed_ms.min(MAX_ELAPSED_MS) as i32;
+
+        // at most two rounds: down to a standstill, then up the other way
+        while elapsed > 0 && self.speed != target {
+            let slowing_down = self.speed != 0
+                && (self.speed.signum() != target.signum() || target.abs() < self.speed.abs());
+            let (rate, goal) = if !slowing_down {
+                (self.config.acceleration as i32, target)
+            } else if self.speed.signum() == target.signum() {
+                (self.config.deceleration as i32, target)
+            } else {
+                (self.config.deceleration as i32, 0)
+            };
+
+            let distance = (goal - self.speed).abs();
+            if rate == 0 || rate * elapsed >= distance {
+                if rate > 0 {
+                    elapsed -= distance / rate;
+                }
+                self.speed = goal;
+            } else {
+                self.speed += (goal - self.speed).signum() * rate * elapsed;
+                elapsed = 0;
+            }
+        }
+
+        self.speed()
+    }
+}
+
+impl Default for Ramp {
+    fn default() -> Self {
+        Self::new(RampConfig::DEFAULT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    // speed after each of `ticks` updates of 10 ms
+    fn profile(ramp: &mut Ramp, ticks: usize) -> Vec<i8> {
+        (0..ticks).map(|_| ramp.update(10)).collect()
+    }
+
+    #[test]
+    fn speeds_up_at_the_acceleration_rate() {
+        let mut ramp = Ramp::default();
+        ramp.set_target(100);
+
+        let speeds = profile(&mut ramp, 60);
+        assert_eq!(speeds[..3], [2, 4, 6]);
+        assert_eq!(speeds[48], 98);
+        assert!(speeds[49..].iter().all(|&speed| speed == 100));
+        assert!(ramp.is_settled());
+    }
+
+    #[test]
+    fn slows_down_at_the_deceleration_rate() {
+        let mut ramp = Ramp::default();
+        ramp.set_target(100);
+        ramp.update(500);
+
+        ramp.set_target(20);
+        let speeds = profile(&mut ramp, 25);
+        assert_eq!(speeds[..3], [96, 92, 88]);
+        assert_eq!(speeds[19], 20);
+        assert!(speeds[19..].iter().all(|&speed| speed == 20));
+    }
+
+    #[test]
+    fn reverses_through_a_standstill() {
+        let mut ramp = Ramp::default();
+        ramp.set_target(10);
+        ramp.update(50);
+
+        // 10 down to 0 takes 25 ms of the 30, the rest speeds up the other way
+        ramp.set_target(-100);
+        assert_eq!(ramp.update(30), -1);
+        assert_eq!(ramp.update(30), -7);
+        // rounded towards 0 on the way
+        assert_eq!(ramp.update(1), -7);
+
+        let speeds = profile(&mut ramp, 50);
+        assert!(speeds.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(ramp.speed(), -100);
+    }
+
+    #[test]
+    fn no_limit_jumps_to_the_target() {
+        let mut ramp = Ramp::new(RampConfig {
+            acceleration: 0,
+            deceleration: 0,
+        });
+
+        ramp.set_target(-70);
+        assert_eq!(ramp.update(1), -70);
+        ramp.set_target(70);
+        assert_eq!(ramp.update(1), 70);
+    }
+
+    #[test]
+    fn clamps_targets_and_long_updates() {
+        let mut ramp = Ramp::default();
+
+        ramp.set_target(i8::MIN);
+        assert_eq!(ramp.target(), -100);
+        assert_eq!(ramp.update(u32::MAX), -100);
+        assert!(ramp.is_settled());
+    }
+
+    #[test]
+    fn emergency_stop_skips_the_ramp() {
+        let mut ramp = Ramp::default();
+        ramp.set_target(100);
+        ramp.update(200);
+
+        ramp.emergency_stop();
+        assert_eq!(ramp.speed(), 0);
+        assert_eq!(ramp.target(), 0);
+        assert_eq!(ramp.update(10), 0);
+    }
+}
@@ -0,0 +1,243 @@
+//! Closed-loop speed control from a wheel encoder.
+
+use super::{Pid, PidConfig, MAX_SPEED};
+
+/// Speed of a shaft from successive encoder positions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tachometer {
+    counts_per_revolution: u32,
+    position: Option<i32>,
+    rpm: f32,
+}
+
+impl Tachometer {
+    /// # Panics
+    ///
+    /// If `counts_per_revolution` is `0`.
+    pub const fn new(counts_per_revolution: u32) -> Self {
+        assert!(counts_per_revolution > 0, "encoder without counts");
+
+        Self {
+            counts_per_revolution,
+            position: None,
+            rpm: 0.0,
+        }
+    }
+
+    /// Takes the encoder position read `elapsed_ms` after the previous one
+    /// and returns the speed in revolutions per minute, `0` on the first
+    /// reading.
+    pub fn update(&mut self, position: i32, elapsed_ms: u32) -> f32 {
+        if let Some(previous) = self.position {
+            if elapsed_ms > 0 {
+                let counts = position.wrapping_sub(previous) as f32;
+                let revolutions = counts / self.counts_per_revolution as f32;
+                self.rpm = revolutions * 60_000.0 / elapsed_ms as f32;
+            }
+        }
+        self.position = Some(position);
+
+        self.rpm
+    }
+
+    pub fn rpm(&self) -> f32 {
+        self.rpm
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.rpm = 0.0;
+    }
+}
+
+/// Holds a motor at a speed in RPM by adjusting its PWM speed.
+///
+/// [`SpeedController::update`] is meant to be called every `sample_period_ms`
+/// from a timer with the encoder position, its result written to the motor,
+/// e.g. with [`super::HBridge::set_speed`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedController {
+    tachometer: Tachometer,
+    pid: Pid,
+    sample_period_ms: u32,
+    target: f32,
+}
+
+impl SpeedController {
+    /// # Panics
+    ///
+    /// If `counts_per_revolution` or `sample_period_ms` is `0`.
+    pub const fn new(counts_per_revolution: u32, sample_period_ms: u32, pid: PidConfig) -> Self {
+        assert!(sample_period_ms > 0, "speed sampled without period");
+
+        Self {
+            tachometer: Tachometer::new(counts_per_revolution),
+            pid: Pid::new(pid),
+            sample_period_ms,
+            target: 0.0,
+        }
+    }
+
+    /// Speed to hold in RPM, negative in reverse.
+    pub fn set_target(&mut self, rpm: f32) {
+        self.target = rpm;
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// Last measured speed in RPM.
+    pub fn rpm(&self) -> f32 {
+        self.tachometer.rpm()
+    }
+
+    pub fn set_pid(&mut self, pid: PidConfig) {
+        self.pid.set_config(pid);
+    }
+
+    pub fn pid(&self) -> &Pid {
+        &self.pid
+    }
+
+    /// Takes the encoder position and returns the motor speed, from `-100`
+    /// to `100`.
+    ///
+    /// A target of `0` lets the motor stop on its own rather than fighting
+    /// every count it drifts by.
+    pub fn update(&mut self, position: i32) -> i8 {
+        self.update_after(position, self.sample_period_ms)
+    }
+
+    /// Same as [`SpeedController::update`] with the position read
+    /// `elapsed_ms` after the previous one rather than a sample period, e.g.
+    /// when samples were missed.
+    pub fn update_after(&mut self, position: i32, elapsed_ms: u32) -> i8 {
+        let rpm = self.tachometer.update(position, elapsed_ms);
+        if self.target == 0.0 {
+            self.pid.reset();
+            return 0;
+        }
+
+        let dt = elapsed_ms as f32 / 1000.0;
+        let output = self.pid.update(self.target, rpm, dt);
+
+        output.clamp(-(MAX_SPEED as f32), MAX_SPEED as f32) as i8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motor::model::FirstOrderMotor;
+
+    const COUNTS_PER_REVOLUTION: u32 = 840;
+    const SAMPLE_PERIOD_MS: u32 = 20;
+    const PID: PidConfig = PidConfig::DEFAULT.with_gains(0.2, 2.0, 0.0);
+
+    fn motor() -> FirstOrderMotor {
+        FirstOrderMotor::new(200.0, 100.0, COUNTS_PER_REVOLUTION)
+    }
+
+    #[test]
+    fn measures_the_speed_between_positions() {
+        let mut tachometer = Tachometer::new(COUNTS_PER_REVOLUTION);
+
+        assert_eq!(tachometer.update(1000, 20), 0.0);
+        assert_eq!(tachometer.update(1000 + 42, 20), 150.0);
+        assert_eq!(tachometer.update(1000, 40), -75.0);
+        // kept when no time went by
+        assert_eq!(tachometer.update(5000, 0), -75.0);
+
+        tachometer.reset();
+        assert_eq!(tachometer.rpm(), 0.0);
+        assert_eq!(tachometer.update(i32::MIN, 20), 0.0);
+        // a count backwards across the wrap
+        let rpm = tachometer.update(i32::MAX, 20);
+        assert!((rpm + 3000.0 / 840.0).abs() < 1e-3, "{rpm}");
+    }
+
+    #[test]
+    fn settles_on_a_step_of_the_target() {
+        let mut motor = motor();
+        let mut controller = SpeedController::new(COUNTS_PER_REVOLUTION, SAMPLE_PERIOD_MS, PID);
+        controller.set_target(120.0);
+
+        let mut peak: f32 = 0.0;
+        let mut settled_at = None;
+        for sample in 1..=100 {
+            let speed = controller.update(motor.position());
+            motor.step(speed, SAMPLE_PERIOD_MS);
+
+            peak = peak.max(motor.rpm());
+            if (motor.rpm() - 120.0).abs() > 6.0 {
+                settled_at = None;
+            } else if settled_at.is_none() {
+                settled_at = Some(sample * SAMPLE_PERIOD_MS);
+            }
+        }
+
+        // within 5% after a second and stays there, without overshooting much
+        let settled_at = settled_at.expect("never settled");
+        assert!(settled_at <= 1000, "settled after {settled_at} ms");
+        assert!(peak < 132.0, "overshot to {peak} rpm");
+        assert!((controller.rpm() - 120.0).abs() < 2.0);
+    }
+
+    #[test]
+    fn saturates_without_winding_up() {
+        let mut motor = motor();
+        let mut controller = SpeedController::new(COUNTS_PER_REVOLUTION, SAMPLE_PERIOD_MS, PID);
+
+        // out of reach: full speed
+        controller.set_target(300.0);
+        for _ in 0..100 {
+            let speed = controller.update(motor.position());
+            assert!(speed <= 100);
+            motor.step(speed, SAMPLE_PERIOD_MS);
+        }
+        assert_eq!(controller.update(motor.position()), 100);
+
+        // back in reach, the integral didn't grow while saturated
+        controller.set_target(100.0);
+        let mut lowest = f32::MAX;
+        for _ in 0..100 {
+            let speed = controller.update(motor.position());
+            motor.step(speed, SAMPLE_PERIOD_MS);
+            lowest = lowest.min(motor.rpm());
+        }
+        assert!(lowest > 90.0, "undershot to {lowest} rpm");
+        assert!((motor.rpm() - 100.0).abs() < 2.0);
+    }
+
+    #[test]
+    fn measures_over_missed_samples() {
+        let mut motor = motor();
+        let mut controller = SpeedController::new(COUNTS_PER_REVOLUTION, SAMPLE_PERIOD_MS, PID);
+        controller.set_target(120.0);
+        for _ in 0..100 {
+            let speed = controller.update(motor.position());
+            motor.step(speed, SAMPLE_PERIOD_MS);
+        }
+
+        // three periods went by since the last sample
+        let speed = controller.update(motor.position());
+        motor.step(speed, 3 * SAMPLE_PERIOD_MS);
+        let speed = controller.update_after(motor.position(), 3 * SAMPLE_PERIOD_MS);
+
+        assert!((controller.rpm() - 120.0).abs() < 2.0);
+        assert!((speed - 60).abs() <= 2, "{speed}");
+    }
+
+    #[test]
+    fn lets_the_motor_stop_on_its_own() {
+        let mut controller = SpeedController::new(COUNTS_PER_REVOLUTION, SAMPLE_PERIOD_MS, PID);
+        controller.set_target(120.0);
+        controller.update(0);
+        assert!(controller.update(0) > 0);
+
+        controller.set_target(0.0);
+        assert_eq!(controller.update(10), 0);
+        assert_eq!(controller.pid().output(), 0.0);
+    }
+}
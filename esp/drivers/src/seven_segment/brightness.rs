@@ -0,0 +1,254 @@
+//! Gamma corrected brightness levels and ambient light dimming.
+
+use embedded_hal::pwm::SetDutyCycle;
+
+use super::Polarity;
+
+pub const MAX_LEVEL: u8 = 15;
+
+/// Duty cycle of each level in per mille, for a gamma of 2.2 so that levels
+/// look evenly spaced.
+pub const GAMMA: [u16; MAX_LEVEL as usize + 1] = [
+    0, 3, 12, 29, 55, 89, 133, 187, 251, 325, 410, 505, 612, 730, 859, 1000,
+];
+
+/// Part of `period` a digit is lit for at `level`.
+pub fn scale(level: u8, period: u32) -> u32 {
+    let duty = GAMMA[level.min(MAX_LEVEL) as usize] as u64;
+    (period as u64 * duty / 1000) as u32
+}
+
+/// Dims a display by PWM on its common pin, or the `OE` pin of a 74HC595.
+pub struct Dimmer<P> {
+    pwm: P,
+    polarity: Polarity,
+    level: u8,
+}
+
+impl<P> Dimmer<P>
+where
+    P: SetDutyCycle,
+{
+    /// `polarity` is the level of `pwm` lighting the display, e.g.
+    /// [`Polarity::ActiveHigh`] for a common cathode switched by an NPN
+    /// transistor and [`Polarity::ActiveLow`] for the `OE` pin of a 74HC595.
+    pub fn new(pwm: P, polarity: Polarity) -> Self {
+        Self {
+            pwm,
+            polarity,
+            level: MAX_LEVEL,
+        }
+    }
+
+    /// Sets the brightness from `0` to [`MAX_LEVEL`].
+    pub fn set_level(&mut self, level: u8) -> Result<(), P::Error> {
+        self.level = level.min(MAX_LEVEL);
+
+        let on = GAMMA[self.level as usize];
+        let duty = match self.polarity {
+            Polarity::ActiveHigh => on,
+            Polarity::ActiveLow => 1000 - on,
+        };
+        self.pwm.set_duty_cycle_fraction(duty, 1000)
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+}
+
+/// Brightness level following the ambient light, read from a light sensor.
+///
+/// Readings are smoothed and the level only changes once the light moved
+/// well past the current level so the display doesn't flicker between two
+/// levels.
+pub struct AutoBrightness {
+    // readings in the dark and in bright light, in any order
+    dark: u16,
+    bright: u16,
+    min_level: u8,
+    max_level: u8,
+    // smoothed reading, times 16
+    filtered: Option<u32>,
+    level: u8,
+}
+
+impl AutoBrightness {
+    /// Levels from `min_level` in the dark to `max_level` in bright light,
+    /// up to [`MAX_LEVEL`], `max_level` raised to `min_level` if below it.
+    pub const fn new(dark: u16, bright: u16, min_level: u8, max_level: u8) -> Self {
+        let min_level = if min_level > MAX_LEVEL {
+            MAX_LEVEL
+        } else {
+            min_level
+        };
+        let max_level = if max_level > MAX_LEVEL {
+            MAX_LEVEL
+        } else if max_level < min_level {
+            min_level
+        } else {
+            max_level
+        };
+
+        Self {
+            dark,
+            bright,
+            min_level,
+            max_level,
+            filtered: None,
+            level: max_level,
+        }
+    }
+
+    /// Feeds a sensor reading and returns the level to use.
+    pub fn update(&mut self, reading: u16) -> u8 {
+        let reading = (reading as u32) << 4;
+        let filtered = match self.filtered {
+            // moves an eighth of the way to the reading
+            Some(filtered) => filtered - filtered / 8 + reading / 8,
+            None => reading,
+        };
+        self.filtered = Some(filtered);
+
+        // light from 0 in the dark to 1 in bright light, times 16
+        let (dark, bright) = ((self.dark as u32) << 4, (self.bright as u32) << 4);
+        let light = if dark < bright {
+            filtered.clamp(dark, bright) - dark
+        } else {
+            dark - filtered.clamp(bright, dark)
+        };
+        let range = dark.abs_diff(bright).max(1);
+
+        let (min, max) = (self.min_level as u32, self.max_level as u32);
+        // target level times 16
+        let target = min * 16 + light * (max - min) * 16 / range;
+
+        // change level when more than 3/4 of a level away from the current one
+        let current = self.level as u32 * 16;
+        if target.abs_diff(current) > 12 {
+            self.level = ((target + 8) / 16) as u8;
+        }
+
+        self.level
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Event, Log};
+
+    #[test]
+    fn scales_the_period_along_the_gamma_curve() {
+        assert_eq!(scale(0, 2000), 0);
+        assert_eq!(scale(1, 2000), 6);
+        assert_eq!(scale(8, 2000), 502);
+        assert_eq!(scale(MAX_LEVEL, 2000), 2000);
+        assert_eq!(scale(200, 2000), 2000);
+        // no overflow on long periods
+        assert_eq!(scale(MAX_LEVEL, u32::MAX), u32::MAX);
+        assert!(GAMMA.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn dims_active_high() {
+        let log = Log::default();
+        let mut dimmer = Dimmer::new(log.pwm("pwm", 1000), Polarity::ActiveHigh);
+        assert_eq!(dimmer.level(), MAX_LEVEL);
+
+        dimmer.set_level(0).unwrap();
+        dimmer.set_level(8).unwrap();
+        dimmer.set_level(99).unwrap();
+        assert_eq!(
+            log.take(),
+            [
+                Event::Duty("pwm", 0),
+                Event::Duty("pwm", 251),
+                Event::Duty("pwm", 1000)
+            ]
+        );
+        assert_eq!(dimmer.level(), MAX_LEVEL);
+    }
+
+    #[test]
+    fn dims_active_low() {
+        let log = Log::default();
+        let mut dimmer = Dimmer::new(log.pwm("oe", 255), Polarity::ActiveLow);
+
+        dimmer.set_level(0).unwrap();
+        dimmer.set_level(8).unwrap();
+        dimmer.set_level(MAX_LEVEL).unwrap();
+        assert_eq!(
+            log.take(),
+            [
+                Event::Duty("oe", 255),
+                Event::Duty("oe", 190),
+                Event::Duty("oe", 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_the_ambient_light() {
+        let mut auto = AutoBrightness::new(100, 900, 2, 12);
+        assert_eq!(auto.level(), 12);
+
+        // the first reading is taken as is
+        assert_eq!(auto.update(100), 2);
+        // then smoothed
+        assert_eq!(auto.update(900), 3);
+        let level = (0..40).fold(0, |_, _| auto.update(900));
+        assert_eq!(level, 12);
+        assert_eq!(auto.update(2000), 12);
+    }
+
+    #[test]
+    fn dark_reading_may_be_the_higher_one() {
+        // e.g. a photoresistor on the high side of the divider
+        let mut auto = AutoBrightness::new(900, 100, 0, MAX_LEVEL);
+
+        assert_eq!(auto.update(900), 0);
+        let mut auto = AutoBrightness::new(900, 100, 0, MAX_LEVEL);
+        assert_eq!(auto.update(100), MAX_LEVEL);
+        let mut auto = AutoBrightness::new(900, 100, 0, MAX_LEVEL);
+        assert_eq!(auto.update(500), 8);
+    }
+
+    #[test]
+    fn keeps_the_level_near_a_boundary() {
+        // a level every 100, 8 being at 800
+        let mut auto = AutoBrightness::new(0, 1500, 0, MAX_LEVEL);
+        assert_eq!(auto.update(750), 8);
+
+        // within 3/4 of a level of 8
+        for reading in [740, 860, 730, 870] {
+            for _ in 0..50 {
+                assert_eq!(auto.update(reading), 8, "at {reading}");
+            }
+        }
+        // further away
+        for _ in 0..50 {
+            auto.update(700);
+        }
+        assert_eq!(auto.level(), 7);
+    }
+
+    #[test]
+    fn clamps_the_levels() {
+        let mut auto = AutoBrightness::new(0, 1000, 40, 30);
+        assert_eq!(auto.level(), MAX_LEVEL);
+        assert_eq!(auto.update(0), MAX_LEVEL);
+        assert_eq!(auto.update(1000), MAX_LEVEL);
+
+        let mut auto = AutoBrightness::new(0, 1000, 10, 3);
+        assert_eq!(auto.level(), 10);
+        assert_eq!(auto.update(1000), 10);
+
+        let mut auto = AutoBrightness::new(0, 1000, 0, 200);
+        assert_eq!(auto.update(1000), MAX_LEVEL);
+    }
+}
@@ -0,0 +1,170 @@
+//! Segment masks for the characters a seven-segment digit can draw.
+//!
+//! Bit 0 is segment `a`, bit 6 is segment `g` and bit 7 is the decimal point:
+//!
+//! ```text
+//!    a
+//!  f   b
+//!    g
+//!  e   c
+//!    d   dp
+//! ```
+
+pub const A: u8 = 1 << 0;
+pub const B: u8 = 1 << 1;
+pub const C: u8 = 1 << 2;
+pub const D: u8 = 1 << 3;
+pub const E: u8 = 1 << 4;
+pub const F: u8 = 1 << 5;
+pub const G: u8 = 1 << 6;
+pub const DP: u8 = 1 << 7;
+
+pub const BLANK: u8 = 0;
+pub const MINUS: u8 = G;
+pub const DEGREE: u8 = A | B | F | G;
+
+/// Masks for the hexadecimal digits `0`..=`F`.
+pub const HEX_DIGITS: [u8; 16] = [
+    A | B | C | D | E | F,     // 0
+    B | C,                     // 1
+    A | B | D | E | G,         // 2
+    A | B | C | D | G,         // 3
+    B | C | F | G,             // 4
+    A | C | D | F | G,         // 5
+    A | C | D | E | F | G,     // 6
+    A | B | C,                 // 7
+    A | B | C | D | E | F | G, // 8
+    A | B | C | D | F | G,     // 9
+    A | B | C | E | F | G,     // A
+    C | D | E | F | G,         // b
+    A | D | E | F,             // C
+    B | C | D | E | G,         // d
+    A | D | E | F | G,         // E
+    A | E | F | G,             // F
+];
+
+/// Mask for a hexadecimal digit, `None` above `0xF`.
+pub fn digit(value: u8) -> Option<u8> {
+    HEX_DIGITS.get(value as usize).copied()
+}
+
+/// Mask for a character, `None` if it can't be drawn on seven segments.
+///
+/// Letters that only exist in one case on a seven-segment display (`b`, `d`,
+/// `n`, `r`, `t`, `y`, `A`, `F`, ...) are accepted in both cases.
+pub fn char(c: char) -> Option<u8> {
+    let mask = match c {
+        '0'..='9' => HEX_DIGITS[c as usize - '0' as usize],
+        'A' | 'a' => HEX_DIGITS[0xA],
+        'B' | 'b' => HEX_DIGITS[0xB],
+        'C' => HEX_DIGITS[0xC],
+        'c' => D | E | G,
+        'D' | 'd' => HEX_DIGITS[0xD],
+        'E' | 'e' => HEX_DIGITS[0xE],
+        'F' | 'f' => HEX_DIGITS[0xF],
+        'G' | 'g' => A | C | D | E | F,
+        'H' => B | C | E | F | G,
+        'h' => C | E | F | G,
+        'I' => E | F,
+        'i' => C,
+        'J' | 'j' => B | C | D | E,
+        'L' | 'l' => D | E | F,
+        'N' | 'n' => C | E | G,
+        'O' => HEX_DIGITS[0],
+        'o' => C | D | E | G,
+        'P' | 'p' => A | B | E | F | G,
+        'Q' | 'q' => A | B | C | F | G,
+        'R' | 'r' => E | G,
+        'S' | 's' => HEX_DIGITS[5],
+        'T' | 't' => D | E | F | G,
+        'U' => B | C | D | E | F,
+        'u' => C | D | E,
+        'Y' | 'y' => B | C | D | F | G,
+        '-' => MINUS,
+        '_' => D,
+        '=' => D | G,
+        '°' => DEGREE,
+        ' ' => BLANK,
+        _ => return None,
+    };
+
+    Some(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digits() {
+        let table = [
+            ('0', 0x3F),
+            ('1', 0x06),
+            ('2', 0x5B),
+            ('3', 0x4F),
+            ('4', 0x66),
+            ('5', 0x6D),
+            ('6', 0x7D),
+            ('7', 0x07),
+            ('8', 0x7F),
+            ('9', 0x6F),
+        ];
+
+        for (c, mask) in table {
+            assert_eq!(char(c), Some(mask), "{c:?}");
+        }
+        for value in 0..=9 {
+            assert_eq!(digit(value), char((b'0' + value) as char));
+        }
+        assert_eq!(digit(0xA), Some(0x77));
+        assert_eq!(digit(0xF), Some(0x71));
+        assert_eq!(digit(0x10), None);
+    }
+
+    #[test]
+    fn letters() {
+        let table = [
+            ('A', 0x77),
+            ('a', 0x77),
+            ('b', 0x7C),
+            ('C', 0x39),
+            ('c', 0x58),
+            ('d', 0x5E),
+            ('E', 0x79),
+            ('F', 0x71),
+            ('H', 0x76),
+            ('h', 0x74),
+            ('L', 0x38),
+            ('n', 0x54),
+            ('o', 0x5C),
+            ('P', 0x73),
+            ('r', 0x50),
+            ('S', 0x6D),
+            ('t', 0x78),
+            ('U', 0x3E),
+            ('u', 0x1C),
+            ('y', 0x6E),
+        ];
+
+        for (c, mask) in table {
+            assert_eq!(char(c), Some(mask), "{c:?}");
+        }
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(char(' '), Some(BLANK));
+        assert_eq!(char('-'), Some(G));
+        assert_eq!(char('_'), Some(D));
+        assert_eq!(char('°'), Some(0x63));
+    }
+
+    #[test]
+    fn unsupported_chars() {
+        for c in [
+            'K', 'k', 'M', 'm', 'V', 'W', 'X', 'x', 'Z', '.', '!', '?', 'é',
+        ] {
+            assert_eq!(char(c), None, "{c:?}");
+        }
+    }
+}
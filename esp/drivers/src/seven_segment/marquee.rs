@@ -0,0 +1,180 @@
+//! Scrolling text with blinking digits, rendered without blocking.
+
+use super::{glyph, text};
+
+/// Text scrolling across `N` digits, up to `L` characters long.
+///
+/// Text that fits is shown still. Longer text starts left-aligned and scrolls
+/// left one digit every `scroll_period` milliseconds, looping with a blank
+/// digit between its end and its start. On top of it, each digit can blink
+/// with a period of `2 * blink_period` milliseconds and have its decimal point
+/// lit.
+///
+/// [`Marquee::frame`] is meant to be called from the main loop with the
+/// current time, its result written to the display.
+pub struct Marquee<const N: usize, const L: usize = 32> {
+    text: [u8; L],
+    len: usize,
+    scroll_period: u64,
+    blink_period: u64,
+    blink: [bool; N],
+    dots: [bool; N],
+    offset: usize,
+    scrolled_at: u64,
+}
+
+impl<const N: usize, const L: usize> Marquee<N, L> {
+    pub const fn new(scroll_period: u64, blink_period: u64) -> Self {
+        Self {
+            text: [glyph::BLANK; L],
+            len: 0,
+            scroll_period,
+            blink_period,
+            blink: [false; N],
+            dots: [false; N],
+            offset: 0,
+            scrolled_at: 0,
+        }
+    }
+
+    /// Shows `text` from its start, see [`text::render_text`].
+    pub fn set_text(&mut self, text: &str, now: u64) {
+        self.len = text::render_text(text, &mut self.text);
+        self.offset = 0;
+        self.scrolled_at = now;
+    }
+
+    pub fn set_scroll_period(&mut self, scroll_period: u64) {
+        self.scroll_period = scroll_period;
+    }
+
+    /// Makes digit `index` (leftmost is `0`) blink or not.
+    pub fn set_blink(&mut self, index: usize, blink: bool) {
+        if let Some(digit) = self.blink.get_mut(index) {
+            *digit = blink;
+        }
+    }
+
+    pub fn set_blink_all(&mut self, blink: bool) {
+        self.blink = [blink; N];
+    }
+
+    /// Lights the decimal point of digit `index` (leftmost is `0`) or not.
+    pub fn set_dot(&mut self, index: usize, dot: bool) {
+        if let Some(digit) = self.dots.get_mut(index) {
+            *digit = dot;
+        }
+    }
+
+    /// Whether the text is too long for the display and scrolls.
+    pub fn is_scrolling(&self) -> bool {
+        self.len > N
+    }
+
+    /// Scrolls the text as time went by and returns the masks to show.
+    pub fn frame(&mut self, now: u64) -> [u8; N] {
+        if self.is_scrolling() && self.scroll_period > 0 {
+            let steps = now.wrapping_sub(self.scrolled_at) / self.scroll_period;
+            self.offset = (self.offset + steps as usize) % self.cycle_len();
+            self.scrolled_at = self.scrolled_at.wrapping_add(steps * self.scroll_period);
+        }
+
+        let blink_off = self.blink_period > 0 && now / self.blink_period % 2 == 1;
+
+        let mut masks = [glyph::BLANK; N];
+        for (index, mask) in masks.iter_mut().enumerate() {
+            if blink_off && self.blink[index] {
+                continue;
+            }
+
+            // text that fits is blank-padded rather than repeated
+            let position = if self.is_scrolling() {
+                (self.offset + index) % self.cycle_len()
+            } else {
+                index
+            };
+            *mask = self.text[..self.len]
+                .get(position)
+                .copied()
+                .unwrap_or(glyph::BLANK);
+            if self.dots[index] {
+                *mask |= glyph::DP;
+            }
+        }
+        masks
+    }
+
+    // the text followed by a blank digit
+    fn cycle_len(&self) -> usize {
+        self.len + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn masks(text: &str) -> [u8; 4] {
+        let mut masks = [glyph::BLANK; 4];
+        text::render_text(text, &mut masks);
+        masks
+    }
+
+    #[test]
+    fn pads_text_that_fits() {
+        let mut marquee: Marquee<4> = Marquee::new(100, 0);
+        marquee.set_text("Hi", 0);
+
+        assert!(!marquee.is_scrolling());
+        assert_eq!(marquee.frame(0), masks("Hi"));
+        assert_eq!(marquee.frame(1000), masks("Hi"));
+
+        marquee.set_text("HoLd", 0);
+        assert_eq!(marquee.frame(1000), masks("HoLd"));
+    }
+
+    #[test]
+    fn scrolls_and_loops_longer_text() {
+        let mut marquee: Marquee<4> = Marquee::new(100, 0);
+        marquee.set_text("donE 1", 0);
+
+        assert!(marquee.is_scrolling());
+        assert_eq!(marquee.frame(0), masks("donE"));
+        assert_eq!(marquee.frame(99), masks("donE"));
+        assert_eq!(marquee.frame(100), masks("onE "));
+        assert_eq!(marquee.frame(300), masks("E 1 "));
+        // a blank digit between the end and the start
+        assert_eq!(marquee.frame(500), masks("1 do"));
+        assert_eq!(marquee.frame(700), masks("donE"));
+    }
+
+    #[test]
+    fn scrolls_from_the_frame_time() {
+        let mut marquee: Marquee<4> = Marquee::new(100, 0);
+        marquee.set_text("123456", 1000);
+
+        // frames skipped by the main loop don't slow the text down
+        assert_eq!(marquee.frame(1250), masks("3456"));
+        assert_eq!(marquee.frame(1300), masks("456 "));
+    }
+
+    #[test]
+    fn blinks_and_lights_dots() {
+        let mut marquee: Marquee<4> = Marquee::new(100, 250);
+        marquee.set_text("Err", 0);
+        marquee.set_blink(0, true);
+        marquee.set_dot(3, true);
+
+        let mut on = masks("Err");
+        on[3] |= glyph::DP;
+        let mut off = on;
+        off[0] = glyph::BLANK;
+
+        assert_eq!(marquee.frame(0), on);
+        assert_eq!(marquee.frame(250), off);
+        assert_eq!(marquee.frame(500), on);
+
+        marquee.set_blink_all(true);
+        assert_eq!(marquee.frame(750), [glyph::BLANK; 4]);
+    }
+}
@@ -0,0 +1,230 @@
+//! MAX7219 LED controller over SPI.
+
+use embedded_hal::spi::SpiDevice;
+
+use super::{glyph, MultiDigitDisplay, SegmentDisplay};
+
+pub const MAX_INTENSITY: u8 = 0x0F;
+pub const MAX_DIGITS: usize = 8;
+
+/// Register map of the MAX7219.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Register {
+    NoOp = 0x00,
+    Digit0 = 0x01,
+    Digit1 = 0x02,
+    Digit2 = 0x03,
+    Digit3 = 0x04,
+    Digit4 = 0x05,
+    Digit5 = 0x06,
+    Digit6 = 0x07,
+    Digit7 = 0x08,
+    /// One bit per digit using the built-in Code B font.
+    DecodeMode = 0x09,
+    /// `0x00` to [`MAX_INTENSITY`].
+    Intensity = 0x0A,
+    /// Number of scanned digits minus one.
+    ScanLimit = 0x0B,
+    /// `0x00` shuts the display down, `0x01` is normal operation.
+    Shutdown = 0x0C,
+    /// `0x01` lights every segment.
+    DisplayTest = 0x0F,
+}
+
+impl Register {
+    /// Register holding digit `index`, `None` above [`Register::Digit7`].
+    pub fn digit(index: usize) -> Option<Self> {
+        const DIGITS: [Register; MAX_DIGITS] = [
+            Register::Digit0,
+            Register::Digit1,
+            Register::Digit2,
+            Register::Digit3,
+            Register::Digit4,
+            Register::Digit5,
+            Register::Digit6,
+            Register::Digit7,
+        ];
+
+        DIGITS.get(index).copied()
+    }
+}
+
+/// Converts a [`glyph`] mask to the no-decode segment order of the MAX7219,
+/// `dp` `a` `b` `c` `d` `e` `f` `g` from bit 7 to bit 0.
+pub fn to_segments(mask: u8) -> u8 {
+    (mask & glyph::DP) | (mask & !glyph::DP).reverse_bits() >> 1
+}
+
+/// `N` digits behind a MAX7219.
+///
+/// `DIG0` is the rightmost digit, as on the usual 8-digit modules. As a
+/// [`SegmentDisplay`], the rightmost digit is written.
+pub struct Max7219<SPI, const N: usize = 8> {
+    spi: SPI,
+    masks: [u8; N],
+}
+
+impl<SPI, const N: usize> Max7219<SPI, N>
+where
+    SPI: SpiDevice,
+{
+    /// Sets the controller up for `N` digits in no-decode mode, blank and at
+    /// full intensity.
+    pub fn new(spi: SPI) -> Result<Self, SPI::Error> {
+        assert!(0 < N && N <= MAX_DIGITS, "the MAX7219 drives 1 to 8 digits");
+
+        let mut max7219 = Self {
+            spi,
+            masks: [glyph::BLANK; N],
+        };
+
+        max7219.write_register(Register::DisplayTest, 0x00)?;
+        max7219.write_register(Register::DecodeMode, 0x00)?;
+        max7219.write_register(Register::ScanLimit, N as u8 - 1)?;
+        max7219.write_register(Register::Intensity, MAX_INTENSITY)?;
+        max7219.write_masks([glyph::BLANK; N])?;
+        max7219.write_register(Register::Shutdown, 0x01)?;
+
+        Ok(max7219)
+    }
+
+    pub fn write_register(&mut self, register: Register, value: u8) -> Result<(), SPI::Error> {
+        self.spi.write(&[register as u8, value])
+    }
+
+    /// Sets the intensity from `0` to [`MAX_INTENSITY`].
+    pub fn set_intensity(&mut self, intensity: u8) -> Result<(), SPI::Error> {
+        self.write_register(Register::Intensity, intensity.min(MAX_INTENSITY))
+    }
+
+    pub fn shutdown(&mut self, shutdown: bool) -> Result<(), SPI::Error> {
+        self.write_register(Register::Shutdown, !shutdown as u8)
+    }
+}
+
+impl<SPI, const N: usize> MultiDigitDisplay<N> for Max7219<SPI, N>
+where
+    SPI: SpiDevice,
+{
+    type Error = SPI::Error;
+
+    fn write_masks(&mut self, masks: [u8; N]) -> Result<(), Self::Error> {
+        for (index, &mask) in masks.iter().rev().enumerate() {
+            if let Some(register) = Register::digit(index) {
+                self.write_register(register, to_segments(mask))?;
+            }
+        }
+
+        self.masks = masks;
+        Ok(())
+    }
+
+    fn masks(&self) -> &[u8; N] {
+        &self.masks
+    }
+}
+
+impl<SPI, const N: usize> SegmentDisplay for Max7219<SPI, N>
+where
+    SPI: SpiDevice,
+{
+    type Error = SPI::Error;
+
+    fn write_mask(&mut self, mask: u8) -> Result<(), Self::Error> {
+        let mut masks = self.masks;
+        masks[N - 1] = mask;
+        self.write_masks(masks)
+    }
+
+    fn mask(&self) -> u8 {
+        self.masks[N - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec;
+
+    use super::*;
+    use crate::mock::{Event, Log};
+
+    fn frame(register: Register, value: u8) -> Event {
+        Event::Spi(vec![register as u8, value])
+    }
+
+    #[test]
+    fn converts_glyphs_to_the_segment_order() {
+        assert_eq!(to_segments(glyph::A), 0b0100_0000);
+        assert_eq!(to_segments(glyph::G), 0b0000_0001);
+        assert_eq!(to_segments(glyph::DP), 0b1000_0000);
+        // 0: a b c d e f
+        assert_eq!(to_segments(0x3F), 0b0111_1110);
+        assert_eq!(to_segments(0xFF), 0xFF);
+    }
+
+    #[test]
+    fn sets_the_controller_up() {
+        let log = Log::default();
+        Max7219::<_, 3>::new(log.spi()).unwrap();
+
+        assert_eq!(
+            log.take(),
+            [
+                frame(Register::DisplayTest, 0x00),
+                frame(Register::DecodeMode, 0x00),
+                frame(Register::ScanLimit, 2),
+                frame(Register::Intensity, MAX_INTENSITY),
+                frame(Register::Digit0, 0x00),
+                frame(Register::Digit1, 0x00),
+                frame(Register::Digit2, 0x00),
+                frame(Register::Shutdown, 0x01),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_the_rightmost_digit_to_dig0() {
+        let log = Log::default();
+        let mut display = Max7219::<_, 3>::new(log.spi()).unwrap();
+        log.take();
+
+        display.write_masks([0x3F, 0x06, glyph::DP]).unwrap();
+        assert_eq!(
+            log.take(),
+            [
+                frame(Register::Digit0, 0b1000_0000),
+                frame(Register::Digit1, 0b0011_0000),
+                frame(Register::Digit2, 0b0111_1110),
+            ]
+        );
+
+        display.write_mask(0x06).unwrap();
+        assert_eq!(SegmentDisplay::mask(&display), 0x06);
+        assert_eq!(display.masks(), &[0x3F, 0x06, 0x06]);
+        assert_eq!(log.take()[0], frame(Register::Digit0, 0b0011_0000));
+    }
+
+    #[test]
+    fn sets_the_intensity_and_shuts_down() {
+        let log = Log::default();
+        let mut display = Max7219::<_, 8>::new(log.spi()).unwrap();
+        log.take();
+
+        display.set_intensity(0x20).unwrap();
+        display.shutdown(true).unwrap();
+        display.shutdown(false).unwrap();
+        assert_eq!(
+            log.take(),
+            [
+                frame(Register::Intensity, MAX_INTENSITY),
+                frame(Register::Shutdown, 0x00),
+                frame(Register::Shutdown, 0x01),
+            ]
+        );
+        assert_eq!(Register::digit(7), Some(Register::Digit7));
+        assert_eq!(Register::digit(8), None);
+    }
+}
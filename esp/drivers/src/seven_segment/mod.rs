@@ -0,0 +1,178 @@
+//! Seven-segment displays.
+
+pub mod brightness;
+pub mod glyph;
+mod marquee;
+pub mod max7219;
+mod multiplexed;
+mod shift_register;
+pub mod text;
+pub mod tm1637;
+mod wiring;
+
+pub use marquee::Marquee;
+pub use max7219::Max7219;
+pub use multiplexed::Multiplexed;
+pub use shift_register::ShiftRegister;
+pub use tm1637::{Tm1637, Tm1637Error};
+pub use wiring::{Polarity, Wiring};
+
+use embedded_hal::digital::{OutputPin, PinState};
+
+/// A single seven-segment digit driven from a segment mask (see [`glyph`]).
+pub trait SegmentDisplay {
+    type Error;
+
+    /// Drives the eight segments from `mask`.
+    fn write_mask(&mut self, mask: u8) -> Result<(), Self::Error>;
+
+    /// The last mask written.
+    fn mask(&self) -> u8;
+
+    /// Shows `number` as a hexadecimal digit, clears the display above `0xF`.
+    fn display(&mut self, number: u8) -> Result<(), Self::Error> {
+        self.write_mask(glyph::digit(number).unwrap_or(glyph::BLANK))
+    }
+
+    /// Shows `c`, clears the display if it has no glyph.
+    fn display_char(&mut self, c: char) -> Result<(), Self::Error> {
+        self.write_mask(glyph::char(c).unwrap_or(glyph::BLANK))
+    }
+
+    /// Lights the decimal point on top of what is displayed.
+    fn dot(&mut self) -> Result<(), Self::Error> {
+        self.write_mask(self.mask() | glyph::DP)
+    }
+
+    fn reset(&mut self) -> Result<(), Self::Error> {
+        self.write_mask(glyph::BLANK)
+    }
+}
+
+/// `N` seven-segment digits, leftmost first, whatever drives them.
+///
+/// Every multi-digit backend implements it, so that swapping backends doesn't
+/// change the calling code.
+pub trait MultiDigitDisplay<const N: usize> {
+    type Error;
+
+    /// Shows one mask per digit.
+    fn write_masks(&mut self, masks: [u8; N]) -> Result<(), Self::Error>;
+
+    /// The masks last written.
+    fn masks(&self) -> &[u8; N];
+
+    /// Shows `number` right-aligned with leading zeros blanked, lighting the
+    /// decimal point of digit `decimal_point`, see [`text::render_number`].
+    fn set_number(&mut self, number: i32, decimal_point: Option<usize>) -> Result<(), Self::Error> {
+        let mut masks = [glyph::BLANK; N];
+        text::render_number(number, decimal_point, &mut masks);
+        self.write_masks(masks)
+    }
+
+    /// Shows `text` left-aligned, see [`text::render_text`].
+    fn set_text(&mut self, text: &str) -> Result<(), Self::Error> {
+        let mut masks = [glyph::BLANK; N];
+        text::render_text(text, &mut masks);
+        self.write_masks(masks)
+    }
+}
+
+/// A seven-segment digit with one GPIO per segment.
+///
+/// By default the pins drive `a`..`g`, `dp` of a common-cathode digit in the
+/// order they are passed to [`SevenSegmentsLed::new`], see
+/// [`SevenSegmentsLed::with_wiring`] for other parts and routings.
+pub struct SevenSegmentsLed<A, B, C, D, E, F, G, DP, OutputPinError>
+where
+    A: OutputPin<Error = OutputPinError>,
+    B: OutputPin<Error = OutputPinError>,
+    C: OutputPin<Error = OutputPinError>,
+    D: OutputPin<Error = OutputPinError>,
+    E: OutputPin<Error = OutputPinError>,
+    F: OutputPin<Error = OutputPinError>,
+    G: OutputPin<Error = OutputPinError>,
+    DP: OutputPin<Error = OutputPinError>,
+{
+    a: A,
+    b: B,
+    c: C,
+    d: D,
+    e: E,
+    f: F,
+    g: G,
+    dp: DP,
+    wiring: Wiring,
+    mask: u8,
+}
+
+impl<A, B, C, D, E, F, G, DP, OutputPinError>
+    SevenSegmentsLed<A, B, C, D, E, F, G, DP, OutputPinError>
+where
+    A: OutputPin<Error = OutputPinError>,
+    B: OutputPin<Error = OutputPinError>,
+    C: OutputPin<Error = OutputPinError>,
+    D: OutputPin<Error = OutputPinError>,
+    E: OutputPin<Error = OutputPinError>,
+    F: OutputPin<Error = OutputPinError>,
+    G: OutputPin<Error = OutputPinError>,
+    DP: OutputPin<Error = OutputPinError>,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(a: A, b: B, c: C, d: D, e: E, f: F, g: G, dp: DP) -> Self {
+        Self {
+            a,
+            b,
+            c,
+            d,
+            e,
+            f,
+            g,
+            dp,
+            wiring: Wiring::default(),
+            mask: glyph::BLANK,
+        }
+    }
+
+    /// Uses `wiring` instead of a common-cathode digit wired in order, the
+    /// pins passed to [`SevenSegmentsLed::new`] being lines 0 to 7.
+    pub fn with_wiring(self, wiring: Wiring) -> Self {
+        Self { wiring, ..self }
+    }
+}
+
+impl<A, B, C, D, E, F, G, DP, OutputPinError> SegmentDisplay
+    for SevenSegmentsLed<A, B, C, D, E, F, G, DP, OutputPinError>
+where
+    A: OutputPin<Error = OutputPinError>,
+    B: OutputPin<Error = OutputPinError>,
+    C: OutputPin<Error = OutputPinError>,
+    D: OutputPin<Error = OutputPinError>,
+    E: OutputPin<Error = OutputPinError>,
+    F: OutputPin<Error = OutputPinError>,
+    G: OutputPin<Error = OutputPinError>,
+    DP: OutputPin<Error = OutputPinError>,
+{
+    type Error = OutputPinError;
+
+    fn write_mask(&mut self, mask: u8) -> Result<(), Self::Error> {
+        let levels = self.wiring.apply(mask);
+        let state = |line: u8| PinState::from(levels & 1 << line != 0);
+
+        self.a.set_state(state(0))?;
+        self.b.set_state(state(1))?;
+        self.c.set_state(state(2))?;
+        self.d.set_state(state(3))?;
+        self.e.set_state(state(4))?;
+        self.f.set_state(state(5))?;
+        self.g.set_state(state(6))?;
+        self.dp.set_state(state(7))?;
+
+        self.mask = mask;
+        Ok(())
+    }
+
+    fn mask(&self) -> u8 {
+        self.mask
+    }
+}
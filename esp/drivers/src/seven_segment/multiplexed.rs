@@ -0,0 +1,205 @@
+//! Multi-digit display sharing one set of segment lines between digits.
+
+use embedded_hal::digital::{OutputPin, PinState};
+
+use super::{brightness, glyph, MultiDigitDisplay, Polarity, SegmentDisplay};
+
+/// `N` digits lit one at a time.
+///
+/// The segment lines are driven through any [`SegmentDisplay`] and each digit
+/// has a select pin, active low by default to pull the common cathode of the
+/// digit low. [`Multiplexed::tick`] must
+/// be called periodically, typically from a timer interrupt, and lights the
+/// next digit each time. A refresh rate of at least `60 * N` ticks per second
+/// avoids visible flicker.
+pub struct Multiplexed<S, D, const N: usize> {
+    segments: S,
+    digits: [D; N],
+    buffer: [u8; N],
+    digit_polarity: Polarity,
+    brightness: u8,
+    current: usize,
+    lit: bool,
+}
+
+impl<S, D, const N: usize> Multiplexed<S, D, N>
+where
+    S: SegmentDisplay,
+    D: OutputPin<Error = S::Error>,
+{
+    pub fn new(segments: S, digits: [D; N]) -> Self {
+        Self {
+            segments,
+            digits,
+            buffer: [glyph::BLANK; N],
+            digit_polarity: Polarity::ActiveLow,
+            brightness: brightness::MAX_LEVEL,
+            current: 0,
+            lit: false,
+        }
+    }
+
+    /// Level selecting a digit, [`Polarity::ActiveHigh`] for common-anode
+    /// parts or when the digits are switched through NPN transistors.
+    pub fn with_digit_polarity(self, digit_polarity: Polarity) -> Self {
+        Self {
+            digit_polarity,
+            ..self
+        }
+    }
+
+    /// Sets the brightness used by [`Multiplexed::tick_dimmed`], from `0` to
+    /// [`brightness::MAX_LEVEL`].
+    pub fn set_brightness(&mut self, level: u8) {
+        self.brightness = level.min(brightness::MAX_LEVEL);
+    }
+
+    /// Switches the current digit off and lights the next one.
+    pub fn tick(&mut self) -> Result<(), S::Error> {
+        if N == 0 {
+            return Ok(());
+        }
+
+        self.select(self.current, false)?;
+        self.current = (self.current + 1) % N;
+        self.segments.write_mask(self.buffer[self.current])?;
+        self.select(self.current, true)?;
+        self.lit = true;
+        Ok(())
+    }
+
+    /// Like [`Multiplexed::tick`] but each digit is only lit for part of
+    /// `slot_us` microseconds depending on the brightness.
+    ///
+    /// Each call either lights the next digit or switches the current one off
+    /// and returns how many microseconds to wait before the next call, to be
+    /// loaded in the timer calling it.
+    pub fn tick_dimmed(&mut self, slot_us: u32) -> Result<u32, S::Error> {
+        let on_us = brightness::scale(self.brightness, slot_us);
+
+        if self.lit && on_us < slot_us {
+            // end of the lit part of the slot
+            self.select(self.current, false)?;
+            self.lit = false;
+            return Ok(slot_us - on_us);
+        }
+
+        self.tick()?;
+        if on_us == 0 {
+            self.select(self.current, false)?;
+            self.lit = false;
+            return Ok(slot_us);
+        }
+        Ok(on_us)
+    }
+
+    /// Blanks every digit.
+    pub fn clear(&mut self) -> Result<(), S::Error> {
+        self.buffer = [glyph::BLANK; N];
+        for index in 0..N {
+            self.select(index, false)?;
+        }
+        self.lit = false;
+        self.segments.reset()
+    }
+
+    fn select(&mut self, index: usize, selected: bool) -> Result<(), S::Error> {
+        let state = PinState::from(self.digit_polarity.is_high(selected));
+        self.digits[index].set_state(state)
+    }
+}
+
+/// The digits are only written to the buffer, shown by the next ticks.
+impl<S, D, const N: usize> MultiDigitDisplay<N> for Multiplexed<S, D, N>
+where
+    S: SegmentDisplay,
+    D: OutputPin<Error = S::Error>,
+{
+    type Error = S::Error;
+
+    fn write_masks(&mut self, masks: [u8; N]) -> Result<(), Self::Error> {
+        self.buffer = masks;
+        Ok(())
+    }
+
+    fn masks(&self) -> &[u8; N] {
+        &self.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Event, Log};
+
+    #[test]
+    fn lights_one_digit_per_tick() {
+        let log = Log::default();
+        let digits = [log.pin("d0"), log.pin("d1"), log.pin("d2")];
+        let mut display = Multiplexed::new(log.segments(), digits);
+        display.set_number(42, None).unwrap();
+
+        let masks = [glyph::BLANK, glyph::HEX_DIGITS[4], glyph::HEX_DIGITS[2]];
+        for tick in 1..=4 {
+            let previous = (tick - 1) % 3;
+            let current = tick % 3;
+            display.tick().unwrap();
+            // the previous digit goes off before the segments change, the
+            // selects being active low
+            assert_eq!(
+                log.take(),
+                [
+                    Event::Pin(["d0", "d1", "d2"][previous], true),
+                    Event::Mask(masks[current]),
+                    Event::Pin(["d0", "d1", "d2"][current], false),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn selects_active_high_digits() {
+        let log = Log::default();
+        let mut display = Multiplexed::new(log.segments(), [log.pin("d0"), log.pin("d1")])
+            .with_digit_polarity(Polarity::ActiveHigh);
+        display.write_masks([glyph::MINUS, glyph::DP]).unwrap();
+
+        display.tick().unwrap();
+        assert_eq!(
+            log.take(),
+            [
+                Event::Pin("d0", false),
+                Event::Mask(glyph::DP),
+                Event::Pin("d1", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn dims_within_the_slot() {
+        let log = Log::default();
+        let mut display = Multiplexed::new(log.segments(), [log.pin("d0"), log.pin("d1")]);
+        display.write_masks([glyph::MINUS; 2]).unwrap();
+        display.set_brightness(0);
+
+        // off for the whole slot at the lowest brightness
+        assert_eq!(display.tick_dimmed(1000).unwrap(), 1000);
+        assert_eq!(log.levels("d1"), [false, true]);
+
+        display.set_brightness(brightness::MAX_LEVEL);
+        assert_eq!(display.tick_dimmed(1000).unwrap(), 1000);
+        assert_eq!(log.levels("d0"), [false]);
+    }
+
+    #[test]
+    fn shows_numbers_with_a_decimal_point() {
+        let log = Log::default();
+        let mut display = Multiplexed::new(log.segments(), [log.pin("d0"), log.pin("d1")]);
+        display.set_number(5, Some(0)).unwrap();
+
+        assert_eq!(
+            display.masks(),
+            &[glyph::HEX_DIGITS[0] | glyph::DP, glyph::HEX_DIGITS[5]]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Seven-segment digits behind 74HC595 shift registers.
+
+use embedded_hal::digital::{OutputPin, PinState};
+
+use super::{glyph, MultiDigitDisplay, SegmentDisplay, Wiring};
+
+/// `N` daisy-chained 74HC595, each driving the eight segments of one digit.
+///
+/// Segment line `n` of a digit is output `Qn` of its register (`QA` being line
+/// 0) and the register nearest the MCU drives the leftmost digit. The 74HC595
+/// is fast enough for the data to be bit-banged without delays. As a
+/// [`SegmentDisplay`], the rightmost digit is written.
+pub struct ShiftRegister<DS, SHCP, STCP, OutputPinError, const N: usize = 1>
+where
+    DS: OutputPin<Error = OutputPinError>,
+    SHCP: OutputPin<Error = OutputPinError>,
+    STCP: OutputPin<Error = OutputPinError>,
+{
+    data: DS,
+    clock: SHCP,
+    latch: STCP,
+    wiring: Wiring,
+    masks: [u8; N],
+}
+
+impl<DS, SHCP, STCP, OutputPinError, const N: usize>
+    ShiftRegister<DS, SHCP, STCP, OutputPinError, N>
+where
+    DS: OutputPin<Error = OutputPinError>,
+    SHCP: OutputPin<Error = OutputPinError>,
+    STCP: OutputPin<Error = OutputPinError>,
+{
+    pub fn new(data: DS, clock: SHCP, latch: STCP) -> Self {
+        Self {
+            data,
+            clock,
+            latch,
+            wiring: Wiring::default(),
+            masks: [glyph::BLANK; N],
+        }
+    }
+
+    /// Uses `wiring` instead of a common-cathode digit on `QA`..`QH` in order.
+    pub fn with_wiring(self, wiring: Wiring) -> Self {
+        Self { wiring, ..self }
+    }
+
+    fn shift_byte(&mut self, levels: u8) -> Result<(), OutputPinError> {
+        // QH is shifted first
+        for line in (0..8).rev() {
+            self.data
+                .set_state(PinState::from(levels & 1 << line != 0))?;
+            self.clock.set_high()?;
+            self.clock.set_low()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<DS, SHCP, STCP, OutputPinError, const N: usize> MultiDigitDisplay<N>
+    for ShiftRegister<DS, SHCP, STCP, OutputPinError, N>
+where
+    DS: OutputPin<Error = OutputPinError>,
+    SHCP: OutputPin<Error = OutputPinError>,
+    STCP: OutputPin<Error = OutputPinError>,
+{
+    type Error = OutputPinError;
+
+    /// Shifts one mask per digit out and latches them.
+    fn write_masks(&mut self, masks: [u8; N]) -> Result<(), Self::Error> {
+        // the first byte shifted out ends up in the register furthest away
+        for &mask in masks.iter().rev() {
+            self.shift_byte(self.wiring.apply(mask))?;
+        }
+
+        self.latch.set_high()?;
+        self.latch.set_low()?;
+
+        self.masks = masks;
+        Ok(())
+    }
+
+    fn masks(&self) -> &[u8; N] {
+        &self.masks
+    }
+}
+
+impl<DS, SHCP, STCP, OutputPinError, const N: usize> SegmentDisplay
+    for ShiftRegister<DS, SHCP, STCP, OutputPinError, N>
+where
+    DS: OutputPin<Error = OutputPinError>,
+    SHCP: OutputPin<Error = OutputPinError>,
+    STCP: OutputPin<Error = OutputPinError>,
+{
+    type Error = OutputPinError;
+
+    fn write_mask(&mut self, mask: u8) -> Result<(), Self::Error> {
+        let mut masks = self.masks;
+        if let Some(last) = masks.last_mut() {
+            *last = mask;
+        }
+        self.write_masks(masks)
+    }
+
+    fn mask(&self) -> u8 {
+        self.masks.last().copied().unwrap_or(glyph::BLANK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::{
+        mock::{Event, Log},
+        seven_segment::Multiplexed,
+    };
+
+    // levels on the data pin, each followed by a clock pulse, QH first
+    fn shifted(levels: u8) -> Vec<Event> {
+        (0..8)
+            .rev()
+            .flat_map(|line| {
+                [
+                    Event::Pin("ds", levels & 1 << line != 0),
+                    Event::Pin("shcp", true),
+                    Event::Pin("shcp", false),
+                ]
+            })
+            .collect()
+    }
+
+    const LATCH: [Event; 2] = [Event::Pin("stcp", true), Event::Pin("stcp", false)];
+
+    #[test]
+    fn shifts_the_rightmost_digit_first() {
+        let log = Log::default();
+        let mut register: ShiftRegister<_, _, _, _, 2> =
+            ShiftRegister::new(log.pin("ds"), log.pin("shcp"), log.pin("stcp"));
+
+        register
+            .write_masks([glyph::A, glyph::DP | glyph::B])
+            .unwrap();
+
+        let mut expected = shifted(glyph::DP | glyph::B);
+        expected.extend(shifted(glyph::A));
+        expected.extend(LATCH);
+        assert_eq!(log.take(), expected);
+        assert_eq!(register.masks(), &[glyph::A, glyph::DP | glyph::B]);
+    }
+
+    #[test]
+    fn applies_the_wiring() {
+        let log = Log::default();
+        let mut register: ShiftRegister<_, _, _, _, 1> =
+            ShiftRegister::new(log.pin("ds"), log.pin("shcp"), log.pin("stcp"))
+                .with_wiring(Wiring::COMMON_ANODE);
+
+        register.display_char('1').unwrap();
+
+        let mut expected = shifted(!(glyph::B | glyph::C));
+        expected.extend(LATCH);
+        assert_eq!(log.take(), expected);
+
+        register.dot().unwrap();
+        assert_eq!(register.mask(), glyph::B | glyph::C | glyph::DP);
+    }
+
+    // the same calling code for every backend
+    fn show<D: MultiDigitDisplay<4>>(display: &mut D) -> Result<(), D::Error> {
+        display.set_number(-42, Some(2))
+    }
+
+    #[test]
+    fn renders_like_the_other_backends() {
+        let log = Log::default();
+        let mut register: ShiftRegister<_, _, _, _, 4> =
+            ShiftRegister::new(log.pin("ds"), log.pin("shcp"), log.pin("stcp"));
+        let mut multiplexed = Multiplexed::new(
+            log.segments(),
+            [log.pin("d0"), log.pin("d1"), log.pin("d2"), log.pin("d3")],
+        );
+
+        show(&mut register).unwrap();
+        show(&mut multiplexed).unwrap();
+
+        assert_eq!(register.masks(), multiplexed.masks());
+        assert_eq!(
+            register.masks(),
+            &[
+                glyph::BLANK,
+                glyph::MINUS,
+                glyph::HEX_DIGITS[4] | glyph::DP,
+                glyph::HEX_DIGITS[2]
+            ]
+        );
+    }
+}
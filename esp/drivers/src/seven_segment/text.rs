@@ -0,0 +1,149 @@
+//! Rendering of numbers and strings into per-digit segment masks.
+
+use super::glyph;
+
+/// Renders `number` right-aligned into `digits`, leftmost digit first.
+///
+/// Leading zeros are blanked, except from the digit carrying the decimal
+/// point onwards so `5` with the point on the second to last digit reads
+/// `0.5`, and `0.05` with it on the third to last. A number that doesn't fit
+/// is shown as dashes.
+pub fn render_number(number: i32, decimal_point: Option<usize>, digits: &mut [u8]) {
+    let len = digits.len();
+    // the digit left of which zeros may be blanked
+    let keep_from = decimal_point
+        .unwrap_or(len.saturating_sub(1))
+        .min(len.saturating_sub(1));
+    let mut value = number.unsigned_abs();
+
+    for (index, digit) in digits.iter_mut().enumerate().rev() {
+        *digit = if value == 0 && index < keep_from {
+            glyph::BLANK
+        } else {
+            glyph::HEX_DIGITS[(value % 10) as usize]
+        };
+        value /= 10;
+    }
+
+    // the sign goes right before the first non blank digit
+    let first = digits
+        .iter()
+        .position(|&mask| mask != glyph::BLANK)
+        .unwrap_or(len);
+    let fits = value == 0 && (number >= 0 || first > 0);
+
+    if !fits {
+        digits.fill(glyph::MINUS);
+        return;
+    }
+    if number < 0 {
+        digits[first - 1] = glyph::MINUS;
+    }
+
+    set_decimal_point(decimal_point, digits);
+}
+
+/// Renders `text` left-aligned into `digits`, leftmost digit first, and
+/// returns the number of digits used.
+///
+/// A `.` lights the decimal point of the previous character instead of taking
+/// a digit of its own. Characters without a glyph are left blank and whatever
+/// doesn't fit is dropped.
+pub fn render_text(text: &str, digits: &mut [u8]) -> usize {
+    digits.fill(glyph::BLANK);
+
+    let mut index = 0;
+    let mut previous_dot = true;
+    for c in text.chars() {
+        if c == '.' && !previous_dot {
+            digits[index - 1] |= glyph::DP;
+            previous_dot = true;
+            continue;
+        }
+        if index == digits.len() {
+            break;
+        }
+
+        digits[index] = if c == '.' {
+            glyph::DP
+        } else {
+            glyph::char(c).unwrap_or(glyph::BLANK)
+        };
+        previous_dot = c == '.';
+        index += 1;
+    }
+
+    index
+}
+
+/// Lights the decimal point of digit `position`, leftmost digit being `0`.
+pub fn set_decimal_point(position: Option<usize>, digits: &mut [u8]) {
+    if let Some(digit) = position.and_then(|position| digits.get_mut(position)) {
+        *digit |= glyph::DP;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(number: i32, decimal_point: Option<usize>) -> [u8; 4] {
+        let mut digits = [0xFF; 4];
+        render_number(number, decimal_point, &mut digits);
+        digits
+    }
+
+    const fn d(value: usize) -> u8 {
+        glyph::HEX_DIGITS[value]
+    }
+
+    #[test]
+    fn blanks_leading_zeros() {
+        const B: u8 = glyph::BLANK;
+
+        assert_eq!(number(42, None), [B, B, d(4), d(2)]);
+        assert_eq!(number(0, None), [B, B, B, d(0)]);
+        assert_eq!(number(1234, None), [d(1), d(2), d(3), d(4)]);
+        assert_eq!(number(-42, None), [B, glyph::MINUS, d(4), d(2)]);
+    }
+
+    #[test]
+    fn keeps_zeros_from_the_decimal_point() {
+        const B: u8 = glyph::BLANK;
+
+        assert_eq!(number(5, Some(2)), [B, B, d(0) | glyph::DP, d(5)]);
+        assert_eq!(number(5, Some(1)), [B, d(0) | glyph::DP, d(0), d(5)]);
+        assert_eq!(
+            number(-5, Some(1)),
+            [glyph::MINUS, d(0) | glyph::DP, d(0), d(5)]
+        );
+        assert_eq!(number(123, Some(3)), [B, d(1), d(2), d(3) | glyph::DP]);
+    }
+
+    #[test]
+    fn dashes_what_does_not_fit() {
+        assert_eq!(number(12345, None), [glyph::MINUS; 4]);
+        assert_eq!(number(-1234, None), [glyph::MINUS; 4]);
+        assert_eq!(number(i32::MIN, None), [glyph::MINUS; 4]);
+    }
+
+    #[test]
+    fn renders_text() {
+        let mut digits = [0xFF; 4];
+
+        assert_eq!(render_text("Hi", &mut digits), 2);
+        assert_eq!(
+            digits,
+            [glyph::char('H').unwrap(), glyph::char('i').unwrap(), 0, 0]
+        );
+
+        assert_eq!(render_text("1.5.", &mut digits), 2);
+        assert_eq!(digits, [d(1) | glyph::DP, d(5) | glyph::DP, 0, 0]);
+
+        assert_eq!(render_text("..", &mut digits), 2);
+        assert_eq!(digits, [glyph::DP, glyph::DP, 0, 0]);
+
+        assert_eq!(render_text("HELLO", &mut digits), 4);
+        assert_eq!(digits[3], glyph::char('L').unwrap());
+    }
+}
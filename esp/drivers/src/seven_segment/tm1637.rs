@@ -0,0 +1,305 @@
+//! TM1637 LED controller, bit-banged over its two-wire interface.
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+
+use super::{glyph, MultiDigitDisplay, SegmentDisplay};
+
+const DATA_AUTO_INCREMENT: u8 = 0x40;
+const ADDRESS: u8 = 0xC0;
+const DISPLAY_OFF: u8 = 0x80;
+const DISPLAY_ON: u8 = 0x88;
+
+// half a clock period, the lines of most modules have capacitors on them
+const BIT_DELAY_US: u32 = 50;
+const ACK_TIMEOUT_US: u32 = 200;
+
+pub const MAX_BRIGHTNESS: u8 = 7;
+pub const MAX_DIGITS: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tm1637Error<E> {
+    Pin(E),
+    /// The controller didn't pull DIO low to acknowledge a byte.
+    AckTimeout,
+}
+
+impl<E> From<E> for Tm1637Error<E> {
+    fn from(error: E) -> Self {
+        Self::Pin(error)
+    }
+}
+
+/// `N` digits behind a TM1637, the grid 0 being the leftmost digit.
+///
+/// `dio` must be an open-drain pin with a pull-up so the controller can
+/// acknowledge each byte. As a [`SegmentDisplay`], the rightmost digit is
+/// written.
+pub struct Tm1637<CLK, DIO, DELAY, OutputPinError, const N: usize = 4>
+where
+    CLK: OutputPin<Error = OutputPinError>,
+    DIO: OutputPin<Error = OutputPinError> + InputPin<Error = OutputPinError>,
+    DELAY: DelayNs,
+{
+    clock: CLK,
+    dio: DIO,
+    delay: DELAY,
+    brightness: u8,
+    masks: [u8; N],
+}
+
+impl<CLK, DIO, DELAY, OutputPinError, const N: usize> Tm1637<CLK, DIO, DELAY, OutputPinError, N>
+where
+    CLK: OutputPin<Error = OutputPinError>,
+    DIO: OutputPin<Error = OutputPinError> + InputPin<Error = OutputPinError>,
+    DELAY: DelayNs,
+{
+    pub fn new(clock: CLK, dio: DIO, delay: DELAY) -> Self {
+        assert!(N <= MAX_DIGITS, "the TM1637 drives up to 6 digits");
+
+        Self {
+            clock,
+            dio,
+            delay,
+            brightness: MAX_BRIGHTNESS,
+            masks: [glyph::BLANK; N],
+        }
+    }
+
+    /// Sets the brightness from `0` to [`MAX_BRIGHTNESS`].
+    pub fn set_brightness(&mut self, brightness: u8) -> Result<(), Tm1637Error<OutputPinError>> {
+        self.brightness = brightness.min(MAX_BRIGHTNESS);
+        self.command(&[DISPLAY_ON | self.brightness])
+    }
+
+    /// Turns the display off, the next write turns it back on.
+    pub fn turn_off(&mut self) -> Result<(), Tm1637Error<OutputPinError>> {
+        self.command(&[DISPLAY_OFF])
+    }
+
+    fn command(&mut self, bytes: &[u8]) -> Result<(), Tm1637Error<OutputPinError>> {
+        self.start()?;
+        for &byte in bytes {
+            self.write_byte(byte)?;
+        }
+        self.stop()
+    }
+
+    fn start(&mut self) -> Result<(), Tm1637Error<OutputPinError>> {
+        self.clock.set_high()?;
+        self.dio.set_high()?;
+        self.bit_delay();
+        self.dio.set_low()?;
+        self.bit_delay();
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), Tm1637Error<OutputPinError>> {
+        self.clock.set_low()?;
+        self.dio.set_low()?;
+        self.bit_delay();
+        self.clock.set_high()?;
+        self.bit_delay();
+        self.dio.set_high()?;
+        self.bit_delay();
+        Ok(())
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<(), Tm1637Error<OutputPinError>> {
+        // least significant bit first, DIO changes while CLK is low
+        for bit in 0..8 {
+            self.clock.set_low()?;
+            if byte & 1 << bit != 0 {
+                self.dio.set_high()?;
+            } else {
+                self.dio.set_low()?;
+            }
+            self.bit_delay();
+            self.clock.set_high()?;
+            self.bit_delay();
+        }
+
+        // release DIO and wait for the controller to pull it low on the 9th clock
+        self.clock.set_low()?;
+        self.dio.set_high()?;
+        self.bit_delay();
+        self.clock.set_high()?;
+
+        let mut waited_us = 0;
+        while self.dio.is_high()? {
+            if waited_us == ACK_TIMEOUT_US {
+                self.clock.set_low()?;
+                return Err(Tm1637Error::AckTimeout);
+            }
+            self.delay.delay_us(1);
+            waited_us += 1;
+        }
+
+        self.bit_delay();
+        self.clock.set_low()?;
+        Ok(())
+    }
+
+    fn bit_delay(&mut self) {
+        self.delay.delay_us(BIT_DELAY_US);
+    }
+}
+
+impl<CLK, DIO, DELAY, OutputPinError, const N: usize> MultiDigitDisplay<N>
+    for Tm1637<CLK, DIO, DELAY, OutputPinError, N>
+where
+    CLK: OutputPin<Error = OutputPinError>,
+    DIO: OutputPin<Error = OutputPinError> + InputPin<Error = OutputPinError>,
+    DELAY: DelayNs,
+{
+    type Error = Tm1637Error<OutputPinError>;
+
+    fn write_masks(&mut self, masks: [u8; N]) -> Result<(), Self::Error> {
+        self.command(&[DATA_AUTO_INCREMENT])?;
+
+        let mut data = [0; MAX_DIGITS + 1];
+        data[0] = ADDRESS;
+        data[1..=N].copy_from_slice(&masks);
+        self.command(&data[..=N])?;
+
+        self.command(&[DISPLAY_ON | self.brightness])?;
+
+        self.masks = masks;
+        Ok(())
+    }
+
+    fn masks(&self) -> &[u8; N] {
+        &self.masks
+    }
+}
+
+impl<CLK, DIO, DELAY, OutputPinError, const N: usize> SegmentDisplay
+    for Tm1637<CLK, DIO, DELAY, OutputPinError, N>
+where
+    CLK: OutputPin<Error = OutputPinError>,
+    DIO: OutputPin<Error = OutputPinError> + InputPin<Error = OutputPinError>,
+    DELAY: DelayNs,
+{
+    type Error = Tm1637Error<OutputPinError>;
+
+    fn write_mask(&mut self, mask: u8) -> Result<(), Self::Error> {
+        let mut masks = self.masks;
+        if let Some(last) = masks.last_mut() {
+            *last = mask;
+        }
+        self.write_masks(masks)
+    }
+
+    fn mask(&self) -> u8 {
+        self.masks.last().copied().unwrap_or(glyph::BLANK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::mock::{Event, Log, Time};
+
+    // byte sent least significant bit first, the acknowledge clock after it
+    fn byte(bits: &[bool; 9]) -> u8 {
+        (0..8).fold(0, |byte, bit| byte | (bits[bit] as u8) << bit)
+    }
+
+    // bytes of each frame between a start and a stop condition, read from the
+    // levels of the lines on the rising edges of the clock
+    fn frames(events: Vec<Event>) -> Vec<Vec<u8>> {
+        let (mut clk, mut dio) = (true, true);
+        let mut frames = Vec::new();
+        let mut bits = Vec::new();
+        for event in events {
+            match event {
+                Event::Pin("clk", high) => {
+                    if high && !clk {
+                        bits.push(dio);
+                    }
+                    clk = high;
+                }
+                Event::Pin("dio", high) => {
+                    if clk && high != dio {
+                        if high {
+                            // 8 bits and the acknowledge per byte, then the
+                            // clock rising before the stop
+                            assert_eq!(bits.len() % 9, 1, "frame cut mid-byte");
+                            let (bytes, _) = bits.as_chunks::<9>();
+                            frames.push(bytes.iter().map(byte).collect());
+                        }
+                        bits.clear();
+                    }
+                    dio = high;
+                }
+                event => panic!("unexpected {event:?}"),
+            }
+        }
+        assert!(bits.is_empty(), "frame without stop");
+        frames
+    }
+
+    #[test]
+    fn writes_the_digits_least_significant_bit_first() {
+        let log = Log::default();
+        let time = Time::default();
+        let mut display: Tm1637<_, _, _, _, 4> =
+            Tm1637::new(log.pin("clk"), log.open_drain("dio", true), time.delay());
+
+        display.write_masks([0x3F, 0x06, 0x5B, 0x80]).unwrap();
+        assert_eq!(
+            frames(log.take()),
+            [
+                std::vec![DATA_AUTO_INCREMENT],
+                std::vec![ADDRESS, 0x3F, 0x06, 0x5B, 0x80],
+                std::vec![DISPLAY_ON | MAX_BRIGHTNESS],
+            ]
+        );
+        assert_eq!(display.masks(), &[0x3F, 0x06, 0x5B, 0x80]);
+        assert_eq!(SegmentDisplay::mask(&display), 0x80);
+    }
+
+    #[test]
+    fn sets_the_brightness() {
+        let log = Log::default();
+        let time = Time::default();
+        let mut display: Tm1637<_, _, _, _, 4> =
+            Tm1637::new(log.pin("clk"), log.open_drain("dio", true), time.delay());
+
+        display.set_brightness(3).unwrap();
+        display.set_brightness(200).unwrap();
+        display.turn_off().unwrap();
+        assert_eq!(
+            frames(log.take()),
+            [
+                [DISPLAY_ON | 3],
+                [DISPLAY_ON | MAX_BRIGHTNESS],
+                [DISPLAY_OFF]
+            ]
+        );
+    }
+
+    #[test]
+    fn gives_up_without_acknowledge() {
+        let log = Log::default();
+        let time = Time::default();
+        let mut display: Tm1637<_, _, _, _, 4> =
+            Tm1637::new(log.pin("clk"), log.open_drain("dio", false), time.delay());
+
+        assert_eq!(display.set_brightness(1), Err(Tm1637Error::AckTimeout));
+        // the byte's bits, then the acknowledge timeout
+        let bit_delays = 2 + 2 * 8 + 1;
+        assert_eq!(
+            time.now(),
+            (bit_delays * BIT_DELAY_US + ACK_TIMEOUT_US) as u64
+        );
+        // the clock is left low
+        assert_eq!(log.levels("clk").last(), Some(&false));
+    }
+}
@@ -0,0 +1,95 @@
+//! Mapping from logical segment masks to the levels of the physical lines.
+
+use super::glyph;
+
+/// Level that lights a segment or selects a digit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Polarity {
+    /// Lit when driven high, e.g. the segments of a common-cathode part.
+    #[default]
+    ActiveHigh,
+    /// Lit when driven low, e.g. the segments of a common-anode part.
+    ActiveLow,
+}
+
+impl Polarity {
+    /// Whether the line must be driven high for `active`.
+    pub fn is_high(self, active: bool) -> bool {
+        active == (self == Polarity::ActiveHigh)
+    }
+}
+
+/// Segment polarity and the segment driven by each of the eight lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Wiring {
+    polarity: Polarity,
+    order: [u8; 8],
+}
+
+impl Wiring {
+    /// Common-cathode part with `a`..`g`, `dp` on lines 0 to 7.
+    pub const COMMON_CATHODE: Self = Self {
+        polarity: Polarity::ActiveHigh,
+        order: [
+            glyph::A,
+            glyph::B,
+            glyph::C,
+            glyph::D,
+            glyph::E,
+            glyph::F,
+            glyph::G,
+            glyph::DP,
+        ],
+    };
+
+    /// Common-anode part with `a`..`g`, `dp` on lines 0 to 7.
+    pub const COMMON_ANODE: Self = Self {
+        polarity: Polarity::ActiveLow,
+        ..Self::COMMON_CATHODE
+    };
+
+    pub fn with_polarity(self, polarity: Polarity) -> Self {
+        Self { polarity, ..self }
+    }
+
+    /// Routes segment `order[line]` (one of the [`glyph`] segment constants)
+    /// to line `line`.
+    ///
+    /// # Panics
+    ///
+    /// If `order` doesn't list every segment exactly once.
+    pub fn with_order(self, order: [u8; 8]) -> Self {
+        let all = order.iter().fold(0, |all, segment| all | segment);
+        assert!(
+            all == 0xFF && order.iter().all(|segment| segment.is_power_of_two()),
+            "segment order must be a permutation of a..g, dp"
+        );
+
+        Self { order, ..self }
+    }
+
+    pub fn polarity(&self) -> Polarity {
+        self.polarity
+    }
+
+    /// Line levels for `mask`, bit `n` set when line `n` must be driven high.
+    pub fn apply(&self, mask: u8) -> u8 {
+        let lit = self
+            .order
+            .iter()
+            .enumerate()
+            .filter(|(_, &segment)| mask & segment != 0)
+            .fold(0u8, |lit, (line, _)| lit | 1 << line);
+
+        match self.polarity {
+            Polarity::ActiveHigh => lit,
+            Polarity::ActiveLow => !lit,
+        }
+    }
+}
+
+impl Default for Wiring {
+    fn default() -> Self {
+        Self::COMMON_CATHODE
+    }
+}
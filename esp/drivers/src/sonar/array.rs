@@ -0,0 +1,356 @@
+//! Several sensors fired one after the other so that they don't hear each
+//! other's echoes.
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+
+use super::{send_trigger, Clock, Distance, EchoTimer, Hcsr04Config, SonarError};
+
+/// Latest result of a sensor and when it came, in clock ticks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SonarReading {
+    pub result: Result<Distance, SonarError>,
+    pub at: u64,
+}
+
+impl SonarReading {
+    /// Whether the sensor gave up waiting for its echo.
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self.result,
+            Err(SonarError::NoEchoStart | SonarError::EchoTimeout)
+        )
+    }
+}
+
+/// Round-robin schedule of `N` sensors, without touching any pin.
+///
+/// Only one sensor measures at a time, the next one firing `guard_us` after
+/// the previous measurement ended so that its late echoes faded out.
+/// [`SonarArray::next_to_fire`] tells when to trigger which sensor and
+/// [`SonarArray::triggered`] when its trigger pulse ended, then the echo of
+/// that sensor goes to [`SonarArray::update`] until it returns a reading.
+pub struct SonarArray<const N: usize> {
+    timer: EchoTimer,
+    guard: u64,
+    next: usize,
+    firing: Option<usize>,
+    ended_at: Option<u64>,
+    readings: [Option<SonarReading>; N],
+}
+
+impl<const N: usize> SonarArray<N> {
+    /// Sensors timed with a clock running at `ticks_per_second`.
+    pub fn new(config: Hcsr04Config, ticks_per_second: u32, guard_us: u32) -> Self {
+        assert!(N > 0, "array without sensors");
+
+        let timer = EchoTimer::new(config, ticks_per_second);
+        let guard = timer.conversion().ticks(guard_us) as u64;
+
+        Self {
+            timer,
+            guard,
+            next: 0,
+            firing: None,
+            ended_at: None,
+            readings: [None; N],
+        }
+    }
+
+    /// Air temperature, in tenths of °C, e.g. read from a sensor.
+    pub fn set_temperature(&mut self, temperature: i16) {
+        self.timer.set_temperature(temperature);
+    }
+
+    /// Sensor to trigger at `now`, if it's time to. It's then counted as
+    /// triggered at `now`, until [`SonarArray::triggered`] tells when its
+    /// trigger pulse actually ended.
+    pub fn next_to_fire(&mut self, now: u64) -> Option<usize> {
+        if self.firing.is_some() {
+            return None;
+        }
+        if let Some(ended_at) = self.ended_at {
+            if now.wrapping_sub(ended_at) < self.guard {
+                return None;
+            }
+        }
+
+        let sensor = self.next;
+        self.next = (self.next + 1) % N;
+        self.firing = Some(sensor);
+        self.timer.start(now);
+
+        Some(sensor)
+    }
+
+    /// Times the echo of the sensor measuring from `now`, once its trigger
+    /// pulse was sent.
+    pub fn triggered(&mut self, now: u64) {
+        if self.firing.is_some() {
+            self.timer.start(now);
+        }
+    }
+
+    /// Sensor measuring, whose echo is expected.
+    pub fn firing(&self) -> Option<usize> {
+        self.firing
+    }
+
+    /// Feeds the echo level of the sensor measuring, returns that sensor once
+    /// its reading came.
+    pub fn update(&mut self, echo_high: bool, now: u64) -> Option<usize> {
+        let sensor = self.firing?;
+        let result = self.timer.update(echo_high, now)?;
+
+        self.readings[sensor] = Some(SonarReading { result, at: now });
+        self.firing = None;
+        self.ended_at = Some(now);
+
+        Some(sensor)
+    }
+
+    /// Latest reading of `sensor`, `None` before its first one.
+    pub fn reading(&self, sensor: usize) -> Option<SonarReading> {
+        self.readings.get(sensor).copied().flatten()
+    }
+
+    pub fn readings(&self) -> &[Option<SonarReading>; N] {
+        &self.readings
+    }
+
+    /// Closest obstacle seen by the latest readings, with its sensor.
+    pub fn closest(&self) -> Option<(usize, Distance)> {
+        self.readings
+            .iter()
+            .enumerate()
+            .filter_map(|(sensor, reading)| Some((sensor, reading.as_ref()?.result.ok()?)))
+            .min_by_key(|&(_, distance)| distance)
+    }
+
+    /// Sensors whose latest reading timed out, e.g. unplugged or facing
+    /// nothing in range.
+    pub fn timed_out(&self) -> impl Iterator<Item = usize> + '_ {
+        self.readings
+            .iter()
+            .enumerate()
+            .filter(|(_, reading)| reading.is_some_and(|reading| reading.is_timeout()))
+            .map(|(sensor, _)| sensor)
+    }
+}
+
+/// `N` HC-SR04 on their own trigger and echo pins, measured in turn.
+///
+/// [`Hcsr04Array::poll`] is meant to be called from the main loop as often
+/// as possible, it never blocks except for the trigger pulse.
+pub struct Hcsr04Array<TRIG, ECHO, CLOCK, DELAY, OutputPinError, const N: usize>
+where
+    TRIG: OutputPin<Error = OutputPinError>,
+    ECHO: InputPin<Error = OutputPinError>,
+    CLOCK: Clock,
+    DELAY: DelayNs,
+{
+    triggers: [TRIG; N],
+    echoes: [ECHO; N],
+    clock: CLOCK,
+    delay: DELAY,
+    array: SonarArray<N>,
+}
+
+impl<TRIG, ECHO, CLOCK, DELAY, OutputPinError, const N: usize>
+    Hcsr04Array<TRIG, ECHO, CLOCK, DELAY, OutputPinError, N>
+where
+    TRIG: OutputPin<Error = OutputPinError>,
+    ECHO: InputPin<Error = OutputPinError>,
+    CLOCK: Clock,
+    DELAY: DelayNs,
+{
+    /// Sensor `i` on `triggers[i]` and `echoes[i]`, the next one firing
+    /// `guard_us` after the previous one got its reading.
+    pub fn new(
+        triggers: [TRIG; N],
+        echoes: [ECHO; N],
+        clock: CLOCK,
+        delay: DELAY,
+        config: Hcsr04Config,
+        guard_us: u32,
+    ) -> Self {
+        let array = SonarArray::new(config, clock.ticks_per_second(), guard_us);
+
+        Self {
+            triggers,
+            echoes,
+            clock,
+            delay,
+            array,
+        }
+    }
+
+    /// Triggers the next sensor when it's time to and checks the echo of the
+    /// sensor measuring. Returns that sensor once its reading came.
+    pub fn poll(&mut self) -> Result<Option<usize>, OutputPinError> {
+        let now = self.clock.now();
+        if let Some(sensor) = self.array.next_to_fire(now) {
+            send_trigger(&mut self.triggers[sensor], &mut self.delay)?;
+            // the sensor only listens once the pulse is over
+            self.array.triggered(self.clock.now());
+            return Ok(None);
+        }
+
+        let Some(sensor) = self.array.firing() else {
+            return Ok(None);
+        };
+        let echo_high = self.echoes[sensor].is_high()?;
+
+        Ok(self.array.update(echo_high, self.clock.now()))
+    }
+
+    pub fn array(&self) -> &SonarArray<N> {
+        &self.array
+    }
+
+    pub fn array_mut(&mut self) -> &mut SonarArray<N> {
+        &mut self.array
+    }
+
+    pub fn release(self) -> ([TRIG; N], [ECHO; N], CLOCK, DELAY) {
+        (self.triggers, self.echoes, self.clock, self.delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::mock::{Log, Time};
+
+    // echo of an obstacle 1 m away at room temperature, in microseconds
+    const ONE_METRE_US: u64 = 5824;
+    const GUARD_US: u32 = 10_000;
+
+    fn array<const N: usize>() -> SonarArray<N> {
+        SonarArray::new(Hcsr04Config::DEFAULT, 1_000_000, GUARD_US)
+    }
+
+    #[test]
+    fn fires_in_turn_after_the_guard() {
+        let mut array = array::<3>();
+
+        assert_eq!(array.next_to_fire(0), Some(0));
+        assert_eq!(array.firing(), Some(0));
+        // one at a time
+        assert_eq!(array.next_to_fire(10), None);
+        assert_eq!(array.update(true, 100), None);
+        assert_eq!(array.update(false, 100 + ONE_METRE_US), Some(0));
+        assert_eq!(array.firing(), None);
+        let ended_at = 100 + ONE_METRE_US;
+
+        assert_eq!(array.next_to_fire(ended_at + GUARD_US as u64 - 1), None);
+        assert_eq!(array.next_to_fire(ended_at + GUARD_US as u64), Some(1));
+        // no echo
+        assert_eq!(array.update(false, 30_000), Some(1));
+
+        assert_eq!(array.next_to_fire(50_000), Some(2));
+        array.update(false, 60_000);
+        assert_eq!(array.next_to_fire(80_000), Some(0));
+    }
+
+    #[test]
+    fn keeps_the_latest_readings() {
+        let mut array = array::<3>();
+        assert_eq!(array.reading(0), None);
+        assert_eq!(array.closest(), None);
+
+        let readings = [(false, 0), (true, 2 * ONE_METRE_US), (true, ONE_METRE_US)];
+        let mut now = 0;
+        for (echo, width) in readings {
+            array.next_to_fire(now).unwrap();
+            if echo {
+                array.update(true, now + 100);
+                array.update(false, now + 100 + width);
+            } else {
+                array.update(false, now + 6000);
+            }
+            now += 100_000;
+        }
+
+        assert_eq!(
+            array.reading(0).map(|reading| reading.result),
+            Some(Err(SonarError::NoEchoStart))
+        );
+        assert!(array.reading(0).unwrap().is_timeout());
+        assert_eq!(
+            array.reading(1).unwrap().at,
+            100_000 + 100 + 2 * ONE_METRE_US
+        );
+        assert_eq!(array.closest(), Some((2, Distance::from_mm(1000))));
+        assert_eq!(array.timed_out().collect::<Vec<_>>(), [0]);
+        assert_eq!(array.reading(3), None);
+    }
+
+    #[test]
+    fn times_the_echo_from_the_end_of_the_trigger() {
+        let mut array = array::<1>();
+
+        array.next_to_fire(0);
+        array.triggered(10);
+        assert_eq!(array.update(false, 5010), None);
+        assert_eq!(array.update(false, 5011), Some(0));
+
+        // ignored when no sensor fires
+        array.triggered(100_000);
+        assert_eq!(array.update(false, 200_000), None);
+    }
+
+    #[test]
+    fn measures_each_sensor_in_turn() {
+        let log = Log::default();
+        let time = Time::default();
+        let echoes = [
+            [(1000, 1000 + ONE_METRE_US)],
+            [(20_000, 20_000 + ONE_METRE_US / 2)],
+        ];
+        let mut sonars = Hcsr04Array::new(
+            [log.pin("t0"), log.pin("t1")],
+            [time.echo(&echoes[0]), time.echo(&echoes[1])],
+            time.clock(),
+            time.delay(),
+            Hcsr04Config::DEFAULT,
+            GUARD_US,
+        );
+
+        let mut readings = Vec::new();
+        while time.now() < 40_000 {
+            if let Some(sensor) = sonars.poll().unwrap() {
+                readings.push((sensor, sonars.array().reading(sensor).unwrap()));
+            }
+        }
+
+        let results: Vec<_> = readings
+            .iter()
+            .map(|(sensor, reading)| (*sensor, reading.result))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                (0, Ok(Distance::from_mm(1000))),
+                (1, Ok(Distance::from_mm(500))),
+                (0, Err(SonarError::NoEchoStart)),
+            ]
+        );
+        // a trigger pulse per measurement
+        assert_eq!(log.levels("t0").len(), 4);
+        assert_eq!(sonars.array().closest(), Some((1, Distance::from_mm(500))));
+        assert_eq!(sonars.array().timed_out().collect::<Vec<_>>(), [0]);
+
+        // sensor 0 fired again the guard after the second reading, and waited
+        // for its echo from the end of its 10 µs trigger pulse
+        let fired_at = readings[1].1.at + GUARD_US as u64;
+        let waited = readings[2].1.at - fired_at;
+        assert!((5010..5020).contains(&waited), "waited {waited} µs");
+    }
+}
@@ -0,0 +1,51 @@
+//! Echo pulses timed from the edges of the echo line, in an interrupt
+//! handler.
+
+/// What happened on the echo line, as sent by an interrupt handler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EchoEvent {
+    /// The sensor sent its burst and waits for the echo.
+    Rose { at: u64 },
+    /// The echo came back `width` ticks after the line rose.
+    Pulse { started_at: u64, width: u32 },
+}
+
+/// Timestamps the edges of the echo line.
+///
+/// [`EchoCapture::on_edge`] is meant to be called from the GPIO interrupt
+/// handler of the echo pin, listening to both edges, with the time in ticks
+/// of a [`super::Clock`]. The events it returns go to the application, e.g. through a
+/// [`crate::channel::Channel`], and from there to
+/// [`super::EchoTimer::on_event`]. As the timestamps are taken right at the
+/// edges, the width doesn't depend on how long the application takes to
+/// handle them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EchoCapture {
+    rose_at: Option<u64>,
+}
+
+impl EchoCapture {
+    pub const fn new() -> Self {
+        Self { rose_at: None }
+    }
+
+    /// Takes the level of the echo line after an edge at `now`.
+    pub fn on_edge(&mut self, echo_high: bool, now: u64) -> Option<EchoEvent> {
+        if echo_high {
+            self.rose_at = Some(now);
+            return Some(EchoEvent::Rose { at: now });
+        }
+
+        // a falling edge without rising one, e.g. right after boot
+        let started_at = self.rose_at.take()?;
+        Some(EchoEvent::Pulse {
+            started_at,
+            width: now.wrapping_sub(started_at) as u32,
+        })
+    }
+
+    /// Whether the echo line is high, a measurement being in flight.
+    pub fn is_in_echo(&self) -> bool {
+        self.rose_at.is_some()
+    }
+}
@@ -0,0 +1,183 @@
+//! Echo time to distance, from the timer tick rate and the air temperature.
+
+use super::Distance;
+
+/// Air temperature assumed when there is no sensor, in tenths of °C.
+pub const ROOM_TEMPERATURE: i16 = 200;
+
+// range the speed of sound is computed for, in tenths of °C
+const MIN_TEMPERATURE: i16 = -400;
+const MAX_TEMPERATURE: i16 = 850;
+
+/// Speed of sound in dry air in mm/s, at `temperature` tenths of °C.
+///
+/// Linear approximation of `331.3 * sqrt(1 + T / 273.15)` m/s, within 0.4 %
+/// from -20 to 50 °C. The temperature is clamped from -40 to 85 °C.
+pub const fn speed_of_sound(temperature: i16) -> u32 {
+    let temperature = if temperature < MIN_TEMPERATURE {
+        MIN_TEMPERATURE
+    } else if temperature > MAX_TEMPERATURE {
+        MAX_TEMPERATURE
+    } else {
+        temperature
+    };
+
+    ((3_313_000 + 606 * temperature as i32) / 10) as u32
+}
+
+/// Converts echo times measured in ticks of a timer to distances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Conversion {
+    ticks_per_second: u32,
+    temperature: i16,
+    speed_of_sound: u32,
+}
+
+impl Conversion {
+    /// Conversion of times in microseconds at room temperature.
+    pub const MICROSECONDS: Self = Self::new(1_000_000);
+
+    /// Conversion of times in ticks of a timer running at `ticks_per_second`,
+    /// at room temperature.
+    ///
+    /// # Panics
+    ///
+    /// If `ticks_per_second` is `0`.
+    pub const fn new(ticks_per_second: u32) -> Self {
+        assert!(ticks_per_second > 0, "timer without ticks");
+
+        Self {
+            ticks_per_second,
+            temperature: ROOM_TEMPERATURE,
+            speed_of_sound: speed_of_sound(ROOM_TEMPERATURE),
+        }
+    }
+
+    /// Air temperature, in tenths of °C.
+    pub const fn with_temperature(self, temperature: i16) -> Self {
+        Self {
+            temperature,
+            speed_of_sound: speed_of_sound(temperature),
+            ..self
+        }
+    }
+
+    /// Air temperature, in tenths of °C, e.g. read from a sensor.
+    pub fn set_temperature(&mut self, temperature: i16) {
+        *self = self.with_temperature(temperature);
+    }
+
+    pub fn temperature(&self) -> i16 {
+        self.temperature
+    }
+
+    /// Speed of sound at the air temperature, in mm/s.
+    pub fn speed_of_sound(&self) -> u32 {
+        self.speed_of_sound
+    }
+
+    pub fn ticks_per_second(&self) -> u32 {
+        self.ticks_per_second
+    }
+
+    /// Distance to the obstacle that sent an echo back after `ticks`, the
+    /// sound going there and back, rounded to the millimetre.
+    pub fn distance(&self, ticks: u32) -> Distance {
+        let round_trip = 2 * self.ticks_per_second as u64;
+        let mm = (ticks as u64 * self.speed_of_sound as u64 + round_trip / 2) / round_trip;
+
+        Distance::from_mm(mm.min(u32::MAX as u64) as u32)
+    }
+
+    /// Time for the echo of an obstacle at `distance` to come back, in ticks.
+    pub fn echo_ticks(&self, distance: Distance) -> u32 {
+        // saturated products are still past the largest result
+        let ticks = (distance.mm() as u64 * 2).saturating_mul(self.ticks_per_second as u64)
+            / self.speed_of_sound as u64;
+
+        ticks.min(u32::MAX as u64) as u32
+    }
+
+    /// `us` microseconds in ticks.
+    pub fn ticks(&self, us: u32) -> u32 {
+        let ticks = us as u64 * self.ticks_per_second as u64 / 1_000_000;
+
+        ticks.min(u32::MAX as u64) as u32
+    }
+}
+
+impl Default for Conversion {
+    fn default() -> Self {
+        Self::MICROSECONDS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    #[test]
+    fn speed_of_sound_follows_the_temperature() {
+        assert_eq!(speed_of_sound(0), 331_300);
+        assert_eq!(speed_of_sound(ROOM_TEMPERATURE), 343_420);
+        assert_eq!(speed_of_sound(-200), 319_180);
+        assert_eq!(speed_of_sound(355), 352_813);
+
+        // clamped to the range
+        assert_eq!(speed_of_sound(i16::MIN), speed_of_sound(-400));
+        assert_eq!(speed_of_sound(i16::MAX), speed_of_sound(850));
+    }
+
+    #[test]
+    fn speed_of_sound_is_close_to_the_exact_one() {
+        for temperature in (-200..=500).step_by(5) {
+            let celsius = temperature as f64 / 10.0;
+            let exact = 331_300.0 * (1.0 + celsius / 273.15).sqrt();
+            let error = (speed_of_sound(temperature) as f64 - exact).abs() / exact;
+
+            assert!(error < 0.004, "{error} at {celsius} °C");
+        }
+    }
+
+    #[test]
+    fn converts_echo_times_across_temperatures() {
+        // the same echo is further away in warmer air
+        let echo_us = 5824;
+        let expected = [(-200, 929), (0, 965), (ROOM_TEMPERATURE, 1000), (400, 1035)];
+        for (temperature, mm) in expected {
+            let conversion = Conversion::MICROSECONDS.with_temperature(temperature);
+
+            assert_eq!(conversion.temperature(), temperature);
+            assert_eq!(conversion.distance(echo_us), Distance::from_mm(mm));
+        }
+    }
+
+    #[test]
+    fn converts_ticks_of_any_timer() {
+        // the 16 MHz system timer of the ESP32-C3
+        let mut conversion = Conversion::new(16_000_000);
+        assert_eq!(conversion.ticks(10), 160);
+        assert_eq!(conversion.distance(16 * 5824), Distance::from_mm(1000));
+
+        for temperature in [-400, 0, 250, 850] {
+            conversion.set_temperature(temperature);
+            for mm in [20, 1000, 4000] {
+                let ticks = conversion.echo_ticks(Distance::from_mm(mm));
+                assert_eq!(conversion.distance(ticks), Distance::from_mm(mm));
+            }
+        }
+    }
+
+    #[test]
+    fn saturates_on_overflow() {
+        let conversion = Conversion::new(1);
+
+        assert_eq!(conversion.ticks(u32::MAX), u32::MAX / 1_000_000);
+        assert_eq!(conversion.distance(u32::MAX), Distance::MAX);
+
+        let conversion = Conversion::new(u32::MAX);
+        assert_eq!(conversion.echo_ticks(Distance::MAX), u32::MAX);
+    }
+}
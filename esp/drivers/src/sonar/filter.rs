@@ -0,0 +1,506 @@
+//! Filters for noisy distance readings, chained into a pipeline.
+//!
+//! Readings and results are [`Estimate`]s: a value with its variance, so that
+//! each stage knows how much to trust what it gets and the application can
+//! show how sure the result is. A pipeline is built with [`Filter::then`]:
+//!
+//! ```
+//! use drivers::sonar::filter::{Filter, Median, OutlierRejection};
+//!
+//! let mut pipeline = OutlierRejection::new(50.0, 3).then(Median::<5>::new());
+//! ```
+
+use super::Distance;
+
+/// Value with its variance, in the unit of the readings and its square.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Estimate {
+    pub value: f32,
+    pub variance: f32,
+}
+
+impl Estimate {
+    /// Reading from a sensor of `variance`, `0` when unknown.
+    pub const fn reading(value: f32, variance: f32) -> Self {
+        Self { value, variance }
+    }
+
+    /// Standard deviation, rounded down.
+    pub fn std_dev(&self) -> u32 {
+        (self.variance.max(0.0) as u32).isqrt()
+    }
+}
+
+/// Reading in millimetres, of unknown variance.
+impl From<Distance> for Estimate {
+    fn from(distance: Distance) -> Self {
+        Self::reading(distance.mm() as f32, 0.0)
+    }
+}
+
+/// A stage of a pipeline.
+pub trait Filter {
+    /// Takes a reading, returns the new estimate or `None` when the reading
+    /// was rejected.
+    fn update(&mut self, reading: Estimate) -> Option<Estimate>;
+
+    /// Forgets the previous readings.
+    fn reset(&mut self);
+
+    /// Feeds the estimates of this filter to `next`.
+    fn then<F: Filter>(self, next: F) -> Chain<Self, F>
+    where
+        Self: Sized,
+    {
+        Chain { first: self, next }
+    }
+}
+
+/// Two filters one after the other, see [`Filter::then`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chain<A, B> {
+    first: A,
+    next: B,
+}
+
+impl<A: Filter, B: Filter> Filter for Chain<A, B> {
+    fn update(&mut self, reading: Estimate) -> Option<Estimate> {
+        self.first
+            .update(reading)
+            .and_then(|estimate| self.next.update(estimate))
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.next.reset();
+    }
+}
+
+/// Median of the last `N` readings, ignoring isolated spikes.
+///
+/// Until `N` readings came, the median of those there are. The variance is
+/// the one of the readings in the window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Median<const N: usize> {
+    window: [f32; N],
+    len: usize,
+    next: usize,
+}
+
+impl<const N: usize> Median<N> {
+    pub const fn new() -> Self {
+        assert!(N > 0, "median of no reading");
+
+        Self {
+            window: [0.0; N],
+            len: 0,
+            next: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for Median<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Filter for Median<N> {
+    fn update(&mut self, reading: Estimate) -> Option<Estimate> {
+        self.window[self.next] = reading.value;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+
+        let mut sorted = self.window;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable_by(f32::total_cmp);
+        let middle = self.len / 2;
+        let value = if self.len.is_multiple_of(2) {
+            (sorted[middle - 1] + sorted[middle]) / 2.0
+        } else {
+            sorted[middle]
+        };
+
+        let mean = sorted.iter().sum::<f32>() / self.len as f32;
+        let variance = sorted
+            .iter()
+            .map(|reading| (reading - mean) * (reading - mean))
+            .sum::<f32>()
+            / self.len as f32;
+
+        Some(Estimate { value, variance })
+    }
+
+    fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+/// Exponential moving average, smoothing with a weight of `alpha` on each new
+/// reading.
+///
+/// The variance is the moving variance of the readings around the average.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ema {
+    alpha: f32,
+    estimate: Option<Estimate>,
+}
+
+impl Ema {
+    /// # Panics
+    ///
+    /// If `alpha` is not in `0.0..=1.0`.
+    pub fn new(alpha: f32) -> Self {
+        assert!((0.0..=1.0).contains(&alpha), "EMA weight out of 0..=1");
+
+        Self {
+            alpha,
+            estimate: None,
+        }
+    }
+}
+
+impl Filter for Ema {
+    fn update(&mut self, reading: Estimate) -> Option<Estimate> {
+        let estimate = match self.estimate {
+            None => reading,
+            Some(Estimate { value, variance }) => {
+                let deviation = reading.value - value;
+                Estimate {
+                    value: value + self.alpha * deviation,
+                    variance: (1.0 - self.alpha) * (variance + self.alpha * deviation * deviation),
+                }
+            }
+        };
+
+        self.estimate = Some(estimate);
+        Some(estimate)
+    }
+
+    fn reset(&mut self) {
+        self.estimate = None;
+    }
+}
+
+/// Drops readings further than `max_jump` from the last one kept, e.g. an
+/// echo from the floor or a lost wave.
+///
+/// After `max_rejected` readings dropped in a row, the next one is kept
+/// anyway: the obstacle really moved.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutlierRejection {
+    max_jump: f32,
+    max_rejected: u8,
+    rejected: u8,
+    last: Option<f32>,
+}
+
+impl OutlierRejection {
+    pub const fn new(max_jump: f32, max_rejected: u8) -> Self {
+        Self {
+            max_jump,
+            max_rejected,
+            rejected: 0,
+            last: None,
+        }
+    }
+
+    /// Readings dropped in a row.
+    pub fn rejected(&self) -> u8 {
+        self.rejected
+    }
+}
+
+impl Filter for OutlierRejection {
+    fn update(&mut self, reading: Estimate) -> Option<Estimate> {
+        let outlier = self
+            .last
+            .is_some_and(|last| (reading.value - last).abs() > self.max_jump);
+        if outlier && self.rejected < self.max_rejected {
+            self.rejected += 1;
+            return None;
+        }
+
+        self.rejected = 0;
+        self.last = Some(reading.value);
+        Some(reading)
+    }
+
+    fn reset(&mut self) {
+        self.rejected = 0;
+        self.last = None;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KalmanConfig {
+    /// How much the distance may change between two readings, as a variance.
+    pub process_noise: f32,
+    /// Variance of the readings that don't come with their own.
+    pub measurement_noise: f32,
+}
+
+impl KalmanConfig {
+    /// HC-SR04 readings in millimetres, a few millimetres apart, of an
+    /// obstacle moving slowly.
+    pub const DEFAULT: Self = Self {
+        process_noise: 4.0,
+        measurement_noise: 25.0,
+    };
+}
+
+impl Default for KalmanConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// One-dimensional Kalman filter of a distance that stays about the same.
+///
+/// Each reading is weighted by how its variance compares to the one of the
+/// estimate, which grows by the process noise between readings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Kalman {
+    config: KalmanConfig,
+    estimate: Option<Estimate>,
+}
+
+impl Kalman {
+    pub const fn new(config: KalmanConfig) -> Self {
+        Self {
+            config,
+            estimate: None,
+        }
+    }
+}
+
+impl Default for Kalman {
+    fn default() -> Self {
+        Self::new(KalmanConfig::DEFAULT)
+    }
+}
+
+impl Filter for Kalman {
+    fn update(&mut self, reading: Estimate) -> Option<Estimate> {
+        let measurement_noise = if reading.variance > 0.0 {
+            reading.variance
+        } else {
+            self.config.measurement_noise
+        };
+
+        let estimate = match self.estimate {
+            None => Estimate {
+                value: reading.value,
+                variance: measurement_noise,
+            },
+            Some(Estimate { value, variance }) => {
+                let predicted = variance + self.config.process_noise;
+                let gain = predicted / (predicted + measurement_noise);
+                Estimate {
+                    value: value + gain * (reading.value - value),
+                    variance: (1.0 - gain) * predicted,
+                }
+            }
+        };
+
+        self.estimate = Some(estimate);
+        Some(estimate)
+    }
+
+    fn reset(&mut self) {
+        self.estimate = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    // an obstacle 1 m away, with a lost wave and an echo from the floor
+    const STILL: [u32; 16] = [
+        1002, 998, 1001, 1004, 3998, 997, 1000, 352, 1003, 999, 1001, 996, 1005, 1000, 998, 1002,
+    ];
+    // the obstacle moved 30 cm further
+    const MOVED: [u32; 10] = [1000, 1001, 999, 1300, 1302, 1298, 1301, 1299, 1300, 1302];
+
+    fn run(filter: &mut impl Filter, trace: &[u32]) -> Vec<Option<Estimate>> {
+        trace
+            .iter()
+            .map(|&mm| filter.update(Distance::from_mm(mm).into()))
+            .collect()
+    }
+
+    fn values(estimates: &[Option<Estimate>]) -> Vec<f32> {
+        estimates
+            .iter()
+            .flatten()
+            .map(|estimate| estimate.value)
+            .collect()
+    }
+
+    #[test]
+    fn median_ignores_spikes() {
+        let mut median = Median::<5>::new();
+        let estimates = run(&mut median, &STILL);
+
+        assert!(estimates.iter().all(Option::is_some));
+        let values = values(&estimates);
+        // the median of the readings so far until the window is full
+        assert_eq!(values[..4], [1002.0, 1000.0, 1001.0, 1001.5]);
+        assert!(values.iter().all(|value| (996.0..=1005.0).contains(value)));
+    }
+
+    #[test]
+    fn median_variance_is_the_window_one() {
+        let mut median = Median::<3>::new();
+        let estimate = run(&mut median, &[990, 1000, 1010])[2].unwrap();
+
+        assert_eq!(estimate.value, 1000.0);
+        assert_eq!(estimate.variance, 200.0 / 3.0);
+        assert_eq!(estimate.std_dev(), 8);
+
+        median.reset();
+        assert_eq!(
+            median.update(Estimate::reading(500.0, 0.0)),
+            Some(Estimate::reading(500.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn median_follows_a_move_after_half_the_window() {
+        let mut median = Median::<5>::new();
+        let values = values(&run(&mut median, &MOVED));
+
+        assert_eq!(values[4], 1001.0);
+        assert_eq!(values[5], 1298.0);
+    }
+
+    #[test]
+    fn ema_smooths_the_readings() {
+        let mut ema = Ema::new(0.5);
+        let values = values(&run(&mut ema, &[1000, 1004, 1000, 1008]));
+
+        assert_eq!(values, [1000.0, 1002.0, 1001.0, 1004.5]);
+    }
+
+    #[test]
+    fn ema_settles_after_a_move() {
+        let mut ema = Ema::new(0.25);
+        let estimates = run(&mut ema, &MOVED);
+        let values = values(&estimates);
+
+        // most of the way after a few readings, not overshooting
+        assert!(values[4] > 1100.0 && values[4] < 1200.0, "{}", values[4]);
+        assert!(values[9] > 1250.0 && values[9] < 1300.0, "{}", values[9]);
+        // the jump shows in the variance
+        let variances: Vec<f32> = estimates.iter().flatten().map(|e| e.variance).collect();
+        assert!(variances[2] < 1.0);
+        assert!(variances[3] > 1000.0);
+
+        ema.reset();
+        assert_eq!(
+            ema.update(Estimate::reading(500.0, 0.0)),
+            Some(Estimate::reading(500.0, 0.0))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "EMA weight out of 0..=1")]
+    fn ema_weight_is_at_most_one() {
+        Ema::new(1.5);
+    }
+
+    #[test]
+    fn outlier_rejection_drops_spikes() {
+        let mut rejection = OutlierRejection::new(50.0, 2);
+        let estimates = run(&mut rejection, &STILL);
+
+        assert_eq!(estimates[4], None);
+        assert_eq!(estimates[7], None);
+        assert_eq!(estimates.iter().flatten().count(), STILL.len() - 2);
+        assert!(values(&estimates)
+            .iter()
+            .all(|value| (996.0..=1005.0).contains(value)));
+    }
+
+    #[test]
+    fn outlier_rejection_follows_a_lasting_move() {
+        let mut rejection = OutlierRejection::new(50.0, 2);
+        let estimates = run(&mut rejection, &MOVED[..5]);
+
+        assert!(estimates[..3].iter().all(Option::is_some));
+        assert_eq!(estimates[3..], [None, None]);
+        assert_eq!(rejection.rejected(), 2);
+
+        // kept anyway, then the readings around it
+        let estimates = run(&mut rejection, &MOVED[5..]);
+        assert_eq!(estimates[0].map(|estimate| estimate.value), Some(1298.0));
+        assert!(estimates.iter().all(Option::is_some));
+        assert_eq!(rejection.rejected(), 0);
+    }
+
+    #[test]
+    fn kalman_reduces_the_noise() {
+        let mut kalman = Kalman::default();
+        let noisy: Vec<u32> = STILL
+            .iter()
+            .copied()
+            .filter(|mm| mm.abs_diff(1000) < 10)
+            .collect();
+        let estimates = run(&mut kalman, &noisy);
+
+        let last = estimates.last().unwrap().unwrap();
+        assert!((last.value - 1000.0).abs() < 2.0, "{}", last.value);
+        // steady state of the variance: p = (p + q) r / (p + q + r)
+        let steady = (-4.0 + (16.0_f32 + 4.0 * 4.0 * 25.0).sqrt()) / 2.0;
+        assert!((last.variance - steady).abs() < 0.1, "{}", last.variance);
+        assert!(last.variance < KalmanConfig::DEFAULT.measurement_noise);
+    }
+
+    #[test]
+    fn kalman_weights_readings_by_their_variance() {
+        let mut kalman = Kalman::new(KalmanConfig {
+            process_noise: 0.0,
+            measurement_noise: 25.0,
+        });
+
+        assert_eq!(
+            kalman.update(Estimate::reading(1000.0, 0.0)),
+            Some(Estimate::reading(1000.0, 25.0))
+        );
+        // as sure as the estimate: half way
+        assert_eq!(
+            kalman.update(Estimate::reading(1010.0, 25.0)),
+            Some(Estimate::reading(1005.0, 12.5))
+        );
+        // much less sure: barely moves it
+        let estimate = kalman.update(Estimate::reading(2000.0, 1e6)).unwrap();
+        assert!((estimate.value - 1005.0).abs() < 0.02, "{}", estimate.value);
+    }
+
+    #[test]
+    fn pipeline_rejects_then_smooths() {
+        let mut pipeline = OutlierRejection::new(50.0, 2)
+            .then(Median::<3>::new())
+            .then(Kalman::default());
+        let estimates = run(&mut pipeline, &STILL);
+
+        assert_eq!(estimates.iter().filter(|e| e.is_none()).count(), 2);
+        assert!(values(&estimates)
+            .iter()
+            .all(|value| (997.0..=1003.0).contains(value)));
+
+        pipeline.reset();
+        assert_eq!(
+            pipeline
+                .update(Estimate::reading(500.0, 0.0))
+                .map(|e| e.value),
+            Some(500.0)
+        );
+    }
+}
@@ -0,0 +1,484 @@
+//! HC-SR04 ultrasonic sensor, measured without blocking forever.
+
+use core::convert::Infallible;
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+
+use super::{Clock, Conversion, Distance, EchoEvent, ROOM_TEMPERATURE};
+
+const TRIGGER_PULSE_US: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SonarError<E = Infallible> {
+    Pin(E),
+    /// The echo line didn't go high after the trigger, e.g. the sensor is
+    /// unplugged.
+    NoEchoStart,
+    /// The echo line didn't go low again, no echo came back.
+    EchoTimeout,
+    /// An echo came back from closer or further than the sensor can tell.
+    OutOfRange,
+}
+
+impl<E> From<E> for SonarError<E> {
+    fn from(error: E) -> Self {
+        Self::Pin(error)
+    }
+}
+
+impl SonarError {
+    /// The same error, from a driver whose pins can fail.
+    pub fn widen<E>(self) -> SonarError<E> {
+        match self {
+            SonarError::Pin(never) => match never {},
+            SonarError::NoEchoStart => SonarError::NoEchoStart,
+            SonarError::EchoTimeout => SonarError::EchoTimeout,
+            SonarError::OutOfRange => SonarError::OutOfRange,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hcsr04Config {
+    pub min_range: Distance,
+    pub max_range: Distance,
+    /// Longest wait for the echo line to go high once triggered, the sensor
+    /// sends its burst first.
+    pub echo_start_timeout_us: u32,
+    /// Air temperature in tenths of °C, until one is read from a sensor.
+    pub temperature: i16,
+}
+
+impl Hcsr04Config {
+    /// The range from the datasheet.
+    pub const DEFAULT: Self = Self {
+        min_range: Distance::from_cm(2),
+        max_range: Distance::from_cm(400),
+        echo_start_timeout_us: 5_000,
+        temperature: ROOM_TEMPERATURE,
+    };
+}
+
+impl Default for Hcsr04Config {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    WaitingEchoStart { triggered_at: u64 },
+    InEcho { started_at: u64 },
+}
+
+/// Sends the pulse starting a measurement on the trigger pin of a sensor,
+/// e.g. when the echo is captured by an interrupt handler.
+pub fn send_trigger<P: OutputPin>(
+    trigger: &mut P,
+    delay: &mut impl DelayNs,
+) -> Result<(), P::Error> {
+    trigger.set_high()?;
+    delay.delay_us(TRIGGER_PULSE_US);
+    trigger.set_low()
+}
+
+/// Times the echo pulse from the level of the echo line, without touching
+/// any pin.
+///
+/// After [`EchoTimer::start`] was called at the trigger, [`EchoTimer::update`]
+/// is fed the polled echo level and the time in ticks until it returns a
+/// result. Edges captured by an interrupt handler go to
+/// [`EchoTimer::on_event`] instead, with [`EchoTimer::check_timeouts`] called
+/// in between.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EchoTimer {
+    config: Hcsr04Config,
+    conversion: Conversion,
+    state: State,
+}
+
+impl EchoTimer {
+    /// Timer of echoes timestamped by a clock running at `ticks_per_second`.
+    pub const fn new(config: Hcsr04Config, ticks_per_second: u32) -> Self {
+        Self {
+            config,
+            conversion: Conversion::new(ticks_per_second).with_temperature(config.temperature),
+            state: State::Idle,
+        }
+    }
+
+    pub fn config(&self) -> &Hcsr04Config {
+        &self.config
+    }
+
+    pub fn conversion(&self) -> &Conversion {
+        &self.conversion
+    }
+
+    /// Air temperature, in tenths of °C, e.g. read from a sensor.
+    pub fn set_temperature(&mut self, temperature: i16) {
+        self.conversion.set_temperature(temperature);
+    }
+
+    /// Longest wait for the echo line to go low again, in ticks: twice the
+    /// echo from the maximum range, so that the pulse a sensor sends when it
+    /// hears nothing (about 38 ms at 4 m) ends before it.
+    pub fn echo_timeout(&self) -> u32 {
+        self.conversion
+            .echo_ticks(self.config.max_range)
+            .saturating_mul(2)
+    }
+
+    /// Starts timing a measurement triggered at `now`.
+    pub fn start(&mut self, now: u64) {
+        self.state = State::WaitingEchoStart { triggered_at: now };
+    }
+
+    /// Whether a measurement is in flight.
+    pub fn is_busy(&self) -> bool {
+        self.state != State::Idle
+    }
+
+    /// Feeds the echo level polled at `now`.
+    pub fn update(&mut self, echo_high: bool, now: u64) -> Option<Result<Distance, SonarError>> {
+        let event = match (self.state, echo_high) {
+            (State::WaitingEchoStart { .. }, true) => EchoEvent::Rose { at: now },
+            (State::InEcho { started_at }, false) => EchoEvent::Pulse {
+                started_at,
+                width: now.wrapping_sub(started_at) as u32,
+            },
+            _ => return self.check_timeouts(now),
+        };
+
+        self.on_event(event)
+    }
+
+    /// Feeds an echo edge captured by an interrupt handler, see
+    /// [`super::EchoCapture`].
+    pub fn on_event(&mut self, event: EchoEvent) -> Option<Result<Distance, SonarError>> {
+        match (self.state, event) {
+            (State::WaitingEchoStart { triggered_at }, EchoEvent::Rose { at })
+                if at >= triggered_at =>
+            {
+                self.state = State::InEcho { started_at: at };
+                None
+            }
+            // the rising edge may have been missed, but ignores the end of an
+            // echo from before the trigger, e.g. of a measurement that timed
+            // out
+            (
+                State::WaitingEchoStart {
+                    triggered_at: since,
+                }
+                | State::InEcho { started_at: since },
+                EchoEvent::Pulse { started_at, width },
+            ) if started_at >= since => {
+                self.state = State::Idle;
+                Some(self.distance(width))
+            }
+            _ => None,
+        }
+    }
+
+    /// Gives up on the echo once a timeout is over, to be called regularly
+    /// while waiting for [`EchoTimer::on_event`].
+    pub fn check_timeouts(&mut self, now: u64) -> Option<Result<Distance, SonarError>> {
+        let error = match self.state {
+            State::Idle => return None,
+            State::WaitingEchoStart { triggered_at } => {
+                let timeout = self.conversion.ticks(self.config.echo_start_timeout_us);
+                if now.wrapping_sub(triggered_at) <= timeout as u64 {
+                    return None;
+                }
+                SonarError::NoEchoStart
+            }
+            State::InEcho { started_at } => {
+                if now.wrapping_sub(started_at) <= self.echo_timeout() as u64 {
+                    return None;
+                }
+                SonarError::EchoTimeout
+            }
+        };
+
+        self.state = State::Idle;
+        Some(Err(error))
+    }
+
+    /// Distance for an echo pulse of `width` ticks, checked against the
+    /// range.
+    pub fn distance(&self, width: u32) -> Result<Distance, SonarError> {
+        let distance = self.conversion.distance(width);
+        if distance < self.config.min_range || distance > self.config.max_range {
+            return Err(SonarError::OutOfRange);
+        }
+
+        Ok(distance)
+    }
+}
+
+/// HC-SR04 on a trigger and an echo pin, timed with a [`Clock`].
+///
+/// [`Hcsr04::measure`] waits for the result, at most the echo timeouts.
+/// [`Hcsr04::trigger`] and [`Hcsr04::poll`] let the caller do something else
+/// meanwhile, as long as it polls often enough for the precision it needs.
+pub struct Hcsr04<TRIG, ECHO, CLOCK, DELAY, OutputPinError>
+where
+    TRIG: OutputPin<Error = OutputPinError>,
+    ECHO: InputPin<Error = OutputPinError>,
+    CLOCK: Clock,
+    DELAY: DelayNs,
+{
+    trigger: TRIG,
+    echo: ECHO,
+    clock: CLOCK,
+    delay: DELAY,
+    timer: EchoTimer,
+}
+
+impl<TRIG, ECHO, CLOCK, DELAY, OutputPinError> Hcsr04<TRIG, ECHO, CLOCK, DELAY, OutputPinError>
+where
+    TRIG: OutputPin<Error = OutputPinError>,
+    ECHO: InputPin<Error = OutputPinError>,
+    CLOCK: Clock,
+    DELAY: DelayNs,
+{
+    pub fn new(trigger: TRIG, echo: ECHO, clock: CLOCK, delay: DELAY) -> Self {
+        let timer = EchoTimer::new(Hcsr04Config::DEFAULT, clock.ticks_per_second());
+
+        Self {
+            trigger,
+            echo,
+            clock,
+            delay,
+            timer,
+        }
+    }
+
+    pub fn with_config(self, config: Hcsr04Config) -> Self {
+        Self {
+            timer: EchoTimer::new(config, self.clock.ticks_per_second()),
+            ..self
+        }
+    }
+
+    /// Air temperature, in tenths of °C, e.g. read from a sensor.
+    pub fn set_temperature(&mut self, temperature: i16) {
+        self.timer.set_temperature(temperature);
+    }
+
+    /// Sends the trigger pulse, starting a measurement.
+    pub fn trigger(&mut self) -> Result<(), SonarError<OutputPinError>> {
+        send_trigger(&mut self.trigger, &mut self.delay)?;
+        self.timer.start(self.clock.now());
+
+        Ok(())
+    }
+
+    /// Checks the echo line, returns the result once the measurement is over.
+    pub fn poll(&mut self) -> Option<Result<Distance, SonarError<OutputPinError>>> {
+        let echo_high = match self.echo.is_high() {
+            Ok(echo_high) => echo_high,
+            Err(error) => return Some(Err(SonarError::Pin(error))),
+        };
+
+        self.timer
+            .update(echo_high, self.clock.now())
+            .map(|result| result.map_err(SonarError::widen))
+    }
+
+    /// Whether a measurement is in flight.
+    pub fn is_busy(&self) -> bool {
+        self.timer.is_busy()
+    }
+
+    /// Triggers and waits for the result.
+    pub fn measure(&mut self) -> Result<Distance, SonarError<OutputPinError>> {
+        self.trigger()?;
+        loop {
+            if let Some(result) = self.poll() {
+                return result;
+            }
+        }
+    }
+
+    pub fn release(self) -> (TRIG, ECHO, CLOCK, DELAY) {
+        (self.trigger, self.echo, self.clock, self.delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Log, Time};
+
+    // echo of an obstacle 1 m away at room temperature, in microseconds
+    const ONE_METRE_US: u64 = 5824;
+
+    #[test]
+    fn measures_the_echo_pulse() {
+        let log = Log::default();
+        let time = Time::default();
+        let pulses = [(100, 100 + ONE_METRE_US)];
+        let mut sonar = Hcsr04::new(
+            log.pin("trig"),
+            time.echo(&pulses),
+            time.clock(),
+            time.delay(),
+        );
+
+        assert_eq!(sonar.measure(), Ok(Distance::from_mm(1000)));
+        assert_eq!(log.levels("trig"), [true, false]);
+        assert!(!sonar.is_busy());
+    }
+
+    #[test]
+    fn polls_without_blocking() {
+        let log = Log::default();
+        let time = Time::default();
+        let pulses = [(1000, 1000 + ONE_METRE_US / 2)];
+        let mut sonar = Hcsr04::new(
+            log.pin("trig"),
+            time.echo(&pulses),
+            time.clock(),
+            time.delay(),
+        );
+
+        sonar.trigger().unwrap();
+        assert!(sonar.is_busy());
+        let mut polls = 0;
+        let result = loop {
+            polls += 1;
+            if let Some(result) = sonar.poll() {
+                break result;
+            }
+        };
+
+        assert_eq!(result, Ok(Distance::from_mm(500)));
+        assert!(polls > ONE_METRE_US / 2);
+        assert!(!sonar.is_busy());
+        assert_eq!(sonar.poll(), None);
+    }
+
+    #[test]
+    fn gives_up_without_echo_start() {
+        let log = Log::default();
+        let time = Time::default();
+        let mut sonar = Hcsr04::new(log.pin("trig"), time.echo(&[]), time.clock(), time.delay());
+
+        assert_eq!(sonar.measure(), Err(SonarError::NoEchoStart));
+        // triggered at 10 µs, then the 5 ms timeout
+        assert_eq!(time.now(), 5012);
+    }
+
+    #[test]
+    fn gives_up_without_echo() {
+        let log = Log::default();
+        let time = Time::default();
+        let pulses = [(100, u64::MAX)];
+        let mut sonar = Hcsr04::new(
+            log.pin("trig"),
+            time.echo(&pulses),
+            time.clock(),
+            time.delay(),
+        );
+
+        assert_eq!(sonar.measure(), Err(SonarError::EchoTimeout));
+        // twice the echo from 4 m
+        let timeout = sonar.timer.echo_timeout() as u64;
+        assert_eq!(timeout, 46_590);
+        assert!((100 + timeout..100 + timeout + 3).contains(&time.now()));
+    }
+
+    #[test]
+    fn rejects_echoes_out_of_range() {
+        let log = Log::default();
+        let time = Time::default();
+        let pulses = [(100, 150), (1000, 1000 + 30_000)];
+        let config = Hcsr04Config {
+            max_range: Distance::from_cm(500),
+            ..Hcsr04Config::DEFAULT
+        };
+        let mut sonar = Hcsr04::new(
+            log.pin("trig"),
+            time.echo(&pulses),
+            time.clock(),
+            time.delay(),
+        )
+        .with_config(config);
+
+        // closer than 2 cm
+        assert_eq!(sonar.measure(), Err(SonarError::OutOfRange));
+        // further than the range
+        assert_eq!(sonar.measure(), Err(SonarError::OutOfRange));
+    }
+
+    #[test]
+    fn times_captured_edges() {
+        let mut timer = EchoTimer::new(Hcsr04Config::DEFAULT, 1_000_000);
+
+        timer.start(0);
+        assert_eq!(timer.on_event(EchoEvent::Rose { at: 200 }), None);
+        assert!(timer.is_busy());
+        assert_eq!(timer.check_timeouts(1000), None);
+        assert_eq!(
+            timer.on_event(EchoEvent::Pulse {
+                started_at: 200,
+                width: ONE_METRE_US as u32
+            }),
+            Some(Ok(Distance::from_mm(1000)))
+        );
+
+        // the rising edge was missed
+        timer.start(10_000);
+        assert_eq!(
+            timer.on_event(EchoEvent::Pulse {
+                started_at: 10_200,
+                width: ONE_METRE_US as u32
+            }),
+            Some(Ok(Distance::from_mm(1000)))
+        );
+
+        // nothing in flight
+        assert_eq!(timer.on_event(EchoEvent::Rose { at: 20_000 }), None);
+        assert!(!timer.is_busy());
+        assert_eq!(timer.check_timeouts(30_000), None);
+    }
+
+    #[test]
+    fn ignores_late_echoes_of_a_timed_out_measurement() {
+        let mut timer = EchoTimer::new(Hcsr04Config::DEFAULT, 1_000_000);
+
+        // no echo for the first measurement
+        timer.start(0);
+        assert_eq!(timer.on_event(EchoEvent::Rose { at: 200 }), None);
+        assert_eq!(
+            timer.check_timeouts(100_000),
+            Some(Err(SonarError::EchoTimeout))
+        );
+
+        // its echo ends once the next one was triggered
+        timer.start(100_010);
+        assert_eq!(
+            timer.on_event(EchoEvent::Pulse {
+                started_at: 200,
+                width: 100_000
+            }),
+            None
+        );
+        assert!(timer.is_busy());
+        assert_eq!(timer.on_event(EchoEvent::Rose { at: 99_000 }), None);
+
+        assert_eq!(timer.on_event(EchoEvent::Rose { at: 100_300 }), None);
+        assert_eq!(
+            timer.on_event(EchoEvent::Pulse {
+                started_at: 100_300,
+                width: ONE_METRE_US as u32
+            }),
+            Some(Ok(Distance::from_mm(1000)))
+        );
+    }
+}
@@ -0,0 +1,465 @@
+//! Measurement modes of a handheld tape measure, cycled with a long press.
+//!
+//! [`Meter`] doesn't measure anything: it tells whether readings are wanted,
+//! is fed them and keeps the value to show, so the whole logic runs on the
+//! host as well:
+//!
+//! ```
+//! use drivers::sonar::{Distance, Meter, MeterMode, MeterValue};
+//!
+//! let mut meter = Meter::<4>::new(2);
+//! assert_eq!(meter.mode(), MeterMode::Single);
+//!
+//! // a press measures a burst of two readings, the last one is shown
+//! assert!(!meter.is_measuring());
+//! meter.press();
+//! meter.update(Some(Distance::from_mm(120)));
+//! meter.update(Some(Distance::from_mm(118)));
+//! assert!(!meter.is_measuring());
+//! assert_eq!(meter.value(), MeterValue::Distance(Distance::from_mm(118)));
+//!
+//! // continuous, then hold freezes the value on a press
+//! meter.long_press();
+//! assert_eq!(meter.mode(), MeterMode::Continuous);
+//! meter.long_press();
+//! meter.update(Some(Distance::from_mm(300)));
+//! meter.press();
+//! assert!(!meter.is_measuring());
+//! meter.update(Some(Distance::from_mm(250)));
+//! assert_eq!(meter.value(), MeterValue::Held(Distance::from_mm(300)));
+//!
+//! // min/max of the readings since entering the mode
+//! meter.long_press();
+//! for mm in [300, 100, 200] {
+//!     meter.update(Some(Distance::from_mm(mm)));
+//! }
+//! assert_eq!(
+//!     meter.value(),
+//!     MeterValue::Range {
+//!         min: Distance::from_mm(100),
+//!         max: Distance::from_mm(300),
+//!     }
+//! );
+//!
+//! // average of the last 4 readings
+//! meter.long_press();
+//! for mm in [500, 100, 200, 300, 400] {
+//!     meter.update(Some(Distance::from_mm(mm)));
+//! }
+//! assert_eq!(
+//!     meter.value(),
+//!     MeterValue::Average {
+//!         mean: Distance::from_mm(250),
+//!         samples: 4,
+//!     }
+//! );
+//!
+//! // and back to single
+//! meter.long_press();
+//! assert_eq!(meter.mode(), MeterMode::Single);
+//! assert_eq!(meter.value(), MeterValue::Nothing);
+//! ```
+
+use core::fmt;
+
+use super::Distance;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeterMode {
+    /// A burst of readings on each press.
+    Single,
+    /// Readings all the time.
+    Continuous,
+    /// Readings all the time, a press freezes the value or lets it go.
+    Hold,
+    /// Closest and furthest readings, a press starts over.
+    MinMax,
+    /// Rolling average of the last readings, a press starts over.
+    Average,
+}
+
+impl MeterMode {
+    /// Mode after this one, back to the first after the last.
+    pub const fn next(self) -> Self {
+        match self {
+            MeterMode::Single => MeterMode::Continuous,
+            MeterMode::Continuous => MeterMode::Hold,
+            MeterMode::Hold => MeterMode::MinMax,
+            MeterMode::MinMax => MeterMode::Average,
+            MeterMode::Average => MeterMode::Single,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            MeterMode::Single => "Single",
+            MeterMode::Continuous => "Continuous",
+            MeterMode::Hold => "Hold",
+            MeterMode::MinMax => "Min/Max",
+            MeterMode::Average => "Average",
+        }
+    }
+}
+
+impl fmt::Display for MeterMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a [`Meter`] shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeterValue {
+    /// No reading yet, or the last one failed, or all of a burst.
+    Nothing,
+    Distance(Distance),
+    /// Frozen by a press in [`MeterMode::Hold`].
+    Held(Distance),
+    Range {
+        min: Distance,
+        max: Distance,
+    },
+    /// Mean of the last `samples` readings.
+    Average {
+        mean: Distance,
+        samples: usize,
+    },
+}
+
+/// Mode state machine of a tape measure, averaging over the last `N`
+/// readings.
+pub struct Meter<const N: usize = 8> {
+    mode: MeterMode,
+    burst: u8,
+    samples_left: u8,
+    held: bool,
+    last: Option<Distance>,
+    range: Option<(Distance, Distance)>,
+    window: [u32; N],
+    len: usize,
+    next: usize,
+}
+
+impl<const N: usize> Meter<N> {
+    /// Starts in [`MeterMode::Single`], measuring `burst` readings per press.
+    ///
+    /// Panics if `burst` is 0, a press would measure nothing.
+    pub const fn new(burst: u8) -> Self {
+        assert!(N > 0, "average of no reading");
+        assert!(burst > 0, "burst of no reading");
+
+        Self {
+            mode: MeterMode::Single,
+            burst,
+            samples_left: 0,
+            held: false,
+            last: None,
+            range: None,
+            window: [0; N],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    pub fn mode(&self) -> MeterMode {
+        self.mode
+    }
+
+    /// Short press of the button. Returns whether a new series of readings
+    /// starts, e.g. to reset a filter.
+    pub fn press(&mut self) -> bool {
+        match self.mode {
+            MeterMode::Single if self.samples_left == 0 => {
+                self.samples_left = self.burst;
+                self.last = None;
+                true
+            }
+            MeterMode::Single | MeterMode::Continuous => false,
+            MeterMode::Hold => {
+                // nothing to freeze before the first reading
+                self.held = !self.held && self.last.is_some();
+                !self.held
+            }
+            MeterMode::MinMax | MeterMode::Average => {
+                self.clear();
+                true
+            }
+        }
+    }
+
+    /// Long press of the button, switches to the next mode and starts over.
+    pub fn long_press(&mut self) -> MeterMode {
+        self.mode = self.mode.next();
+        self.clear();
+        self.mode
+    }
+
+    /// Whether readings are wanted.
+    pub fn is_measuring(&self) -> bool {
+        match self.mode {
+            MeterMode::Single => self.samples_left > 0,
+            MeterMode::Hold => !self.held,
+            MeterMode::Continuous | MeterMode::MinMax | MeterMode::Average => true,
+        }
+    }
+
+    /// Takes a reading, `None` when it failed. Ignored when not measuring.
+    pub fn update(&mut self, reading: Option<Distance>) {
+        if !self.is_measuring() {
+            return;
+        }
+
+        self.samples_left = self.samples_left.saturating_sub(1);
+        self.last = match self.mode {
+            // a burst shows its last good reading
+            MeterMode::Single => reading.or(self.last),
+            _ => reading,
+        };
+        let Some(distance) = reading else {
+            return;
+        };
+
+        self.range = Some(match self.range {
+            None => (distance, distance),
+            Some((min, max)) => (min.min(distance), max.max(distance)),
+        });
+
+        self.window[self.next] = distance.mm();
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    /// Value to show in the current mode.
+    pub fn value(&self) -> MeterValue {
+        match self.mode {
+            MeterMode::Single | MeterMode::Continuous => {
+                self.last.map_or(MeterValue::Nothing, MeterValue::Distance)
+            }
+            MeterMode::Hold => match self.last {
+                None => MeterValue::Nothing,
+                Some(distance) if self.held => MeterValue::Held(distance),
+                Some(distance) => MeterValue::Distance(distance),
+            },
+            MeterMode::MinMax => {
+                self.range
+                    .map_or(MeterValue::Nothing, |(min, max)| MeterValue::Range {
+                        min,
+                        max,
+                    })
+            }
+            MeterMode::Average if self.len == 0 => MeterValue::Nothing,
+            MeterMode::Average => {
+                let sum: u64 = self.window[..self.len].iter().map(|&mm| mm as u64).sum();
+                let len = self.len as u64;
+                MeterValue::Average {
+                    mean: Distance::from_mm(((sum + len / 2) / len) as u32),
+                    samples: self.len,
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.samples_left = 0;
+        self.held = false;
+        self.last = None;
+        self.range = None;
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+impl<const N: usize> Default for Meter<N> {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn mm(mm: u32) -> Distance {
+        Distance::from_mm(mm)
+    }
+
+    fn meter_in(mode: MeterMode) -> Meter<4> {
+        let mut meter = Meter::new(3);
+        while meter.mode() != mode {
+            meter.long_press();
+        }
+        meter
+    }
+
+    #[test]
+    #[should_panic(expected = "burst of no reading")]
+    fn rejects_an_empty_burst() {
+        Meter::<4>::new(0);
+    }
+
+    #[test]
+    fn cycles_through_the_modes() {
+        let mut meter = Meter::<4>::default();
+        let modes = [
+            MeterMode::Continuous,
+            MeterMode::Hold,
+            MeterMode::MinMax,
+            MeterMode::Average,
+            MeterMode::Single,
+        ];
+        for mode in modes {
+            assert_eq!(meter.long_press(), mode);
+        }
+    }
+
+    #[test]
+    fn single_keeps_the_last_good_reading_of_a_burst() {
+        let mut meter = meter_in(MeterMode::Single);
+        assert!(meter.press());
+        meter.update(Some(mm(120)));
+        meter.update(None);
+        assert!(meter.is_measuring());
+        meter.update(None);
+        assert!(!meter.is_measuring());
+        assert_eq!(meter.value(), MeterValue::Distance(mm(120)));
+
+        // a failed burst shows nothing rather than the previous one
+        assert!(meter.press());
+        for _ in 0..3 {
+            meter.update(None);
+        }
+        assert_eq!(meter.value(), MeterValue::Nothing);
+    }
+
+    #[test]
+    fn single_ignores_presses_during_a_burst() {
+        let mut meter = meter_in(MeterMode::Single);
+        assert!(meter.press());
+        meter.update(Some(mm(100)));
+        assert!(!meter.press());
+        meter.update(Some(mm(110)));
+        meter.update(Some(mm(120)));
+        assert!(!meter.is_measuring());
+
+        // nor readings after it
+        meter.update(Some(mm(500)));
+        assert_eq!(meter.value(), MeterValue::Distance(mm(120)));
+    }
+
+    #[test]
+    fn continuous_shows_failed_readings() {
+        let mut meter = meter_in(MeterMode::Continuous);
+        assert!(meter.is_measuring());
+        meter.update(Some(mm(300)));
+        assert!(!meter.press());
+        assert_eq!(meter.value(), MeterValue::Distance(mm(300)));
+        meter.update(None);
+        assert_eq!(meter.value(), MeterValue::Nothing);
+    }
+
+    #[test]
+    fn hold_needs_a_reading_to_freeze() {
+        let mut meter = meter_in(MeterMode::Hold);
+        assert!(meter.press());
+        assert!(meter.is_measuring());
+        assert_eq!(meter.value(), MeterValue::Nothing);
+
+        meter.update(Some(mm(300)));
+        assert!(!meter.press());
+        assert!(!meter.is_measuring());
+        meter.update(Some(mm(250)));
+        assert_eq!(meter.value(), MeterValue::Held(mm(300)));
+
+        // a press lets it go
+        assert!(meter.press());
+        assert_eq!(meter.value(), MeterValue::Distance(mm(300)));
+        meter.update(None);
+        assert_eq!(meter.value(), MeterValue::Nothing);
+        // and a failed reading can't be frozen
+        assert!(meter.press());
+        assert!(meter.is_measuring());
+    }
+
+    #[test]
+    fn min_max_skips_failed_readings() {
+        let mut meter = meter_in(MeterMode::MinMax);
+        meter.update(None);
+        assert_eq!(meter.value(), MeterValue::Nothing);
+        for reading in [Some(mm(300)), None, Some(mm(100)), Some(mm(200))] {
+            meter.update(reading);
+        }
+        assert_eq!(
+            meter.value(),
+            MeterValue::Range {
+                min: mm(100),
+                max: mm(300)
+            }
+        );
+
+        assert!(meter.press());
+        assert_eq!(meter.value(), MeterValue::Nothing);
+    }
+
+    #[test]
+    fn min_max_starts_over_with_the_mode() {
+        let mut meter = meter_in(MeterMode::MinMax);
+        meter.update(Some(mm(100)));
+        meter.update(Some(mm(900)));
+
+        // all the way round
+        for _ in 0..5 {
+            meter.long_press();
+        }
+        assert_eq!(meter.mode(), MeterMode::MinMax);
+        assert_eq!(meter.value(), MeterValue::Nothing);
+        meter.update(Some(mm(500)));
+        assert_eq!(
+            meter.value(),
+            MeterValue::Range {
+                min: mm(500),
+                max: mm(500)
+            }
+        );
+    }
+
+    #[test]
+    fn average_rolls_over_the_last_readings() {
+        let mut meter = meter_in(MeterMode::Average);
+        meter.update(None);
+        assert_eq!(meter.value(), MeterValue::Nothing);
+
+        meter.update(Some(mm(100)));
+        meter.update(Some(mm(201)));
+        meter.update(None);
+        assert_eq!(
+            meter.value(),
+            MeterValue::Average {
+                mean: mm(151),
+                samples: 2
+            }
+        );
+
+        // the window wraps twice, only the last 4 count
+        for reading in [1000, 1000, 1000, 1000, 1000, 10, 20, 30, 40] {
+            meter.update(Some(mm(reading)));
+        }
+        assert_eq!(
+            meter.value(),
+            MeterValue::Average {
+                mean: mm(25),
+                samples: 4
+            }
+        );
+
+        assert!(meter.press());
+        assert_eq!(meter.value(), MeterValue::Nothing);
+        meter.update(Some(mm(70)));
+        assert_eq!(
+            meter.value(),
+            MeterValue::Average {
+                mean: mm(70),
+                samples: 1
+            }
+        );
+    }
+}
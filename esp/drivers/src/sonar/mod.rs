@@ -0,0 +1,60 @@
+//! Ultrasonic distance sensors.
+
+pub mod filter;
+
+mod array;
+mod capture;
+mod conversion;
+mod hcsr04;
+mod meter;
+mod zones;
+
+use core::fmt;
+
+pub use array::{Hcsr04Array, SonarArray, SonarReading};
+pub use capture::{EchoCapture, EchoEvent};
+pub use conversion::{speed_of_sound, Conversion, ROOM_TEMPERATURE};
+pub use hcsr04::{send_trigger, EchoTimer, Hcsr04, Hcsr04Config, SonarError};
+pub use meter::{Meter, MeterMode, MeterValue};
+pub use zones::{Beeper, Pattern, Zone, ZoneAlert};
+
+/// Free-running time source, e.g. a hardware timer.
+pub trait Clock {
+    /// Current time, in ticks.
+    fn now(&self) -> u64;
+
+    fn ticks_per_second(&self) -> u32;
+}
+
+/// Distance in millimetres.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Distance(u32);
+
+impl Distance {
+    /// Further than any sensor measures.
+    pub const MAX: Self = Self(u32::MAX);
+
+    pub const fn from_mm(mm: u32) -> Self {
+        Self(mm)
+    }
+
+    pub const fn from_cm(cm: u32) -> Self {
+        Self(cm * 10)
+    }
+
+    pub const fn mm(self) -> u32 {
+        self.0
+    }
+
+    /// Whole centimetres, rounded down.
+    pub const fn cm(self) -> u32 {
+        self.0 / 10
+    }
+}
+
+/// Shown in centimetres with a decimal, e.g. `12.3 cm`.
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{} cm", self.0 / 10, self.0 % 10)
+    }
+}
@@ -0,0 +1,351 @@
+//! Distance zones driving indicator outputs, like a parking sensor.
+
+use super::Distance;
+
+/// What an output does while in a zone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    Off,
+    On,
+    /// On for `on_ms` then off for `off_ms`, starting on when entering the
+    /// zone.
+    Blink {
+        on_ms: u32,
+        off_ms: u32,
+    },
+}
+
+impl Pattern {
+    /// Whether the output is on `elapsed_ms` after entering the zone.
+    pub fn is_on(&self, elapsed_ms: u64) -> bool {
+        match *self {
+            Pattern::Off => false,
+            Pattern::On => true,
+            Pattern::Blink { on_ms, off_ms } => {
+                let period = on_ms as u64 + off_ms as u64;
+                period == 0 || elapsed_ms % period < on_ms as u64
+            }
+        }
+    }
+}
+
+/// Distances up to `max` included, and what each of the `O` outputs does
+/// there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Zone<const O: usize> {
+    pub max: Distance,
+    pub outputs: [Pattern; O],
+}
+
+/// Picks the zone of each distance and the state of the outputs.
+///
+/// `zones` go from the closest to the furthest, the last one usually up to
+/// [`Distance::MAX`]. Leaving a zone takes going `hysteresis` past its bound,
+/// so that the outputs don't flicker when the obstacle is right on it.
+/// [`ZoneAlert::outputs`] is meant to be called from the main loop as often
+/// as the blink patterns need.
+pub struct ZoneAlert<'a, const O: usize> {
+    zones: &'a [Zone<O>],
+    hysteresis: Distance,
+    // `None` before the first distance, `zones.len()` when outside of the
+    // zones
+    zone: Option<usize>,
+    entered_at: u64,
+}
+
+impl<'a, const O: usize> ZoneAlert<'a, O> {
+    /// # Panics
+    ///
+    /// If `zones` is empty or not sorted by distance.
+    pub fn new(zones: &'a [Zone<O>], hysteresis: Distance) -> Self {
+        assert!(!zones.is_empty(), "alert without zones");
+        assert!(
+            zones.windows(2).all(|pair| pair[0].max < pair[1].max),
+            "zones not sorted by distance"
+        );
+
+        Self {
+            zones,
+            hysteresis,
+            zone: None,
+            entered_at: 0,
+        }
+    }
+
+    /// Takes a distance measured at `now` milliseconds, returns the index of
+    /// its zone or `None` when further than the last one.
+    pub fn update(&mut self, distance: Distance, now: u64) -> Option<usize> {
+        let mm = distance.mm();
+        let hysteresis = self.hysteresis.mm();
+        let zone = self.position(mm);
+
+        // only counted in a zone further or closer once past the bound by the
+        // hysteresis, the first distance going straight to its zone
+        let zone = match self.zone {
+            Some(current) if zone > current => {
+                self.position(mm.saturating_sub(hysteresis)).max(current)
+            }
+            Some(current) if zone < current => {
+                self.position(mm.saturating_add(hysteresis)).min(current)
+            }
+            _ => zone,
+        };
+
+        if self.zone != Some(zone) {
+            self.zone = Some(zone);
+            self.entered_at = now;
+        }
+        self.zone()
+    }
+
+    /// Zone of the last distance, `None` before the first one or when
+    /// further than the last zone.
+    pub fn zone(&self) -> Option<usize> {
+        self.zone.filter(|&zone| zone < self.zones.len())
+    }
+
+    /// State of each output at `now` milliseconds, all off outside of the
+    /// zones.
+    pub fn outputs(&self, now: u64) -> [bool; O] {
+        let Some(zone) = self.zone().map(|zone| &self.zones[zone]) else {
+            return [false; O];
+        };
+
+        let elapsed = now.wrapping_sub(self.entered_at);
+        zone.outputs.map(|pattern| pattern.is_on(elapsed))
+    }
+
+    /// Index of the zone of `mm`, the number of zones past the last one.
+    fn position(&self, mm: u32) -> usize {
+        self.zones
+            .iter()
+            .position(|zone| mm <= zone.max.mm())
+            .unwrap_or(self.zones.len())
+    }
+}
+
+/// Beeps faster as the obstacle gets closer, continuously from `near` and
+/// not at all past `far`.
+pub struct Beeper {
+    near: Distance,
+    far: Distance,
+    beep_ms: u64,
+    fastest_ms: u64,
+    slowest_ms: u64,
+    period: Option<u64>,
+    started_at: u64,
+}
+
+impl Beeper {
+    /// Beeps of `beep_ms` repeated every `fastest_ms` near to `slowest_ms`
+    /// far.
+    ///
+    /// # Panics
+    ///
+    /// If `near` is not closer than `far`, or `fastest_ms` is longer than
+    /// `slowest_ms`.
+    pub fn new(
+        near: Distance,
+        far: Distance,
+        beep_ms: u64,
+        fastest_ms: u64,
+        slowest_ms: u64,
+    ) -> Self {
+        assert!(near < far, "beeper near bound past its far bound");
+        assert!(fastest_ms <= slowest_ms, "beeper slower near than far");
+
+        Self {
+            near,
+            far,
+            beep_ms,
+            fastest_ms,
+            slowest_ms,
+            period: None,
+            started_at: 0,
+        }
+    }
+
+    /// Takes the last distance measured.
+    pub fn set_distance(&mut self, distance: Distance) {
+        self.period = if distance <= self.near {
+            Some(0)
+        } else if distance > self.far {
+            None
+        } else {
+            let span = (self.far.mm() - self.near.mm()) as u64;
+            let offset = (distance.mm() - self.near.mm()) as u64;
+            Some(self.fastest_ms + (self.slowest_ms - self.fastest_ms) * offset / span)
+        };
+    }
+
+    /// Stops beeping until the next distance.
+    pub fn silence(&mut self) {
+        self.period = None;
+    }
+
+    /// Whether the buzzer is on at `now` milliseconds.
+    pub fn is_on(&mut self, now: u64) -> bool {
+        match self.period {
+            None => false,
+            Some(0) => true,
+            Some(period) => {
+                // a beep always plays until its end, the period taking effect
+                // from the next one
+                if now.wrapping_sub(self.started_at) >= period.max(self.beep_ms) {
+                    self.started_at = now;
+                }
+                now.wrapping_sub(self.started_at) < self.beep_ms
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLINK: Pattern = Pattern::Blink {
+        on_ms: 100,
+        off_ms: 300,
+    };
+
+    // the zones of the parking sensor
+    const ZONES: [Zone<3>; 4] = [
+        Zone {
+            max: Distance::from_mm(49),
+            outputs: [BLINK, Pattern::Off, Pattern::Off],
+        },
+        Zone {
+            max: Distance::from_cm(10),
+            outputs: [Pattern::On, Pattern::Off, Pattern::Off],
+        },
+        Zone {
+            max: Distance::from_cm(20),
+            outputs: [Pattern::Off, Pattern::On, Pattern::Off],
+        },
+        Zone {
+            max: Distance::MAX,
+            outputs: [Pattern::Off, Pattern::Off, Pattern::On],
+        },
+    ];
+
+    const HYSTERESIS: Distance = Distance::from_mm(5);
+
+    #[test]
+    fn blinks() {
+        assert!(BLINK.is_on(0));
+        assert!(BLINK.is_on(99));
+        assert!(!BLINK.is_on(100));
+        assert!(!BLINK.is_on(399));
+        assert!(BLINK.is_on(400));
+        assert!(Pattern::Blink {
+            on_ms: 0,
+            off_ms: 0
+        }
+        .is_on(123));
+        assert!(!Pattern::Off.is_on(0));
+    }
+
+    #[test]
+    fn first_distance_goes_straight_to_its_zone() {
+        let mut alert = ZoneAlert::new(&ZONES, HYSTERESIS);
+        assert_eq!(alert.zone(), None);
+        assert_eq!(alert.outputs(0), [false; 3]);
+
+        assert_eq!(alert.update(Distance::from_mm(97), 0), Some(1));
+        assert_eq!(alert.outputs(0), [true, false, false]);
+
+        let mut alert = ZoneAlert::new(&ZONES, HYSTERESIS);
+        assert_eq!(alert.update(Distance::from_mm(203), 0), Some(3));
+    }
+
+    #[test]
+    fn leaves_a_zone_past_the_hysteresis() {
+        let mut alert = ZoneAlert::new(&ZONES, HYSTERESIS);
+        alert.update(Distance::from_mm(97), 0);
+
+        // further
+        assert_eq!(alert.update(Distance::from_mm(104), 60), Some(1));
+        assert_eq!(alert.update(Distance::from_mm(106), 120), Some(2));
+        // closer
+        assert_eq!(alert.update(Distance::from_mm(96), 180), Some(2));
+        assert_eq!(alert.update(Distance::from_mm(94), 240), Some(1));
+        // straight past several zones
+        assert_eq!(alert.update(Distance::from_mm(300), 300), Some(3));
+        assert_eq!(alert.update(Distance::from_mm(10), 360), Some(0));
+    }
+
+    #[test]
+    fn blinks_from_entering_the_zone() {
+        let mut alert = ZoneAlert::new(&ZONES, HYSTERESIS);
+        alert.update(Distance::from_mm(150), 0);
+        alert.update(Distance::from_mm(40), 1000);
+
+        assert_eq!(alert.outputs(1000), [true, false, false]);
+        assert_eq!(alert.outputs(1150), [false, false, false]);
+        // staying in the zone doesn't restart it
+        alert.update(Distance::from_mm(30), 1200);
+        assert_eq!(alert.outputs(1250), [false, false, false]);
+        assert_eq!(alert.outputs(1400), [true, false, false]);
+    }
+
+    #[test]
+    fn turns_everything_off_past_the_last_zone() {
+        let zones = &ZONES[..3];
+        let mut alert = ZoneAlert::new(zones, HYSTERESIS);
+
+        assert_eq!(alert.update(Distance::from_mm(300), 0), None);
+        assert_eq!(alert.outputs(0), [false; 3]);
+        // in and back out only past the hysteresis
+        assert_eq!(alert.update(Distance::from_mm(198), 60), None);
+        assert_eq!(alert.update(Distance::from_mm(194), 120), Some(2));
+        assert_eq!(alert.update(Distance::from_mm(203), 180), Some(2));
+        assert_eq!(alert.update(Distance::from_mm(206), 240), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "zones not sorted by distance")]
+    fn zones_are_sorted() {
+        ZoneAlert::new(&[ZONES[2], ZONES[1]], HYSTERESIS);
+    }
+
+    fn beeper() -> Beeper {
+        Beeper::new(Distance::from_cm(5), Distance::from_cm(25), 50, 100, 500)
+    }
+
+    #[test]
+    fn beeps_faster_closer() {
+        let mut beeper = beeper();
+        assert!(!beeper.is_on(0));
+
+        // every 300 ms half way
+        beeper.set_distance(Distance::from_cm(15));
+        let beeps: [bool; 5] = [0, 49, 50, 299, 300].map(|now| beeper.is_on(now));
+        assert_eq!(beeps, [true, true, false, false, true]);
+
+        beeper.set_distance(Distance::from_cm(5));
+        assert!((1000..2000).all(|now| beeper.is_on(now)));
+
+        beeper.set_distance(Distance::from_mm(251));
+        assert!(!(2000..3000).any(|now| beeper.is_on(now)));
+    }
+
+    #[test]
+    fn finishes_a_beep_before_speeding_up() {
+        let mut beeper = beeper();
+        beeper.set_distance(Distance::from_cm(25));
+        assert!(beeper.is_on(0));
+
+        // every 102 ms from the start of the beep playing
+        beeper.set_distance(Distance::from_mm(51));
+        assert!(beeper.is_on(40));
+        assert!(!beeper.is_on(60));
+        assert!(!beeper.is_on(101));
+        assert!(beeper.is_on(102));
+        assert!(beeper.is_on(151));
+        assert!(!beeper.is_on(152));
+
+        beeper.silence();
+        assert!(!beeper.is_on(210));
+    }
+}
@@ -0,0 +1,378 @@
+//! Power-cut safe storage of a single value in NOR flash.
+//!
+//! Values are appended as checksummed records to one of two flash sectors.
+//! Once a sector is full the other one is erased and used, so each sector is
+//! erased once every few hundred writes and the latest complete record is
+//! never erased. A record torn by a power cut fails its checksum and the one
+//! before it is loaded instead.
+
+use embedded_storage::nor_flash::NorFlash;
+
+const SECTORS: u32 = 2;
+const RECORD_SIZE: u32 = 12;
+// sequence number of an erased slot
+const ERASED: u32 = u32::MAX;
+
+/// Latest value written to two flash sectors starting at `base`.
+pub struct RecordStore<F> {
+    flash: F,
+    base: u32,
+    // where the next record goes and its sequence number
+    sector: u32,
+    slot: u32,
+    sequence: u32,
+    value: Option<i32>,
+}
+
+impl<F> RecordStore<F>
+where
+    F: NorFlash,
+{
+    // checked when building for a flash whose writes or reads can't be a
+    // record long
+    const RECORD_ALIGNED: () = assert!(
+        (RECORD_SIZE as usize).is_multiple_of(F::WRITE_SIZE)
+            && (RECORD_SIZE as usize).is_multiple_of(F::READ_SIZE)
+            && RECORD_SIZE as usize <= F::ERASE_SIZE,
+        "records don't fit the write and read sizes of the flash"
+    );
+
+    /// # Panics
+    ///
+    /// If `base` isn't aligned on a sector.
+    pub fn new(flash: F, base: u32) -> Self {
+        let () = Self::RECORD_ALIGNED;
+        assert!(
+            base.is_multiple_of(Self::sector_size()),
+            "record store base isn't sector aligned"
+        );
+
+        Self {
+            flash,
+            base,
+            sector: 0,
+            slot: 0,
+            sequence: 0,
+            value: None,
+        }
+    }
+
+    /// Finds the latest complete record, `None` if nothing was ever stored.
+    pub fn load(&mut self) -> Result<Option<i32>, F::Error> {
+        // sequence, value, sector and used slots of the latest record
+        let mut latest: Option<(u32, i32, u32, u32)> = None;
+
+        for sector in 0..SECTORS {
+            let mut sector_latest: Option<(u32, i32)> = None;
+            let mut used = 0;
+
+            while used < Self::slots_per_sector() {
+                let mut record = [0; RECORD_SIZE as usize];
+                self.flash.read(self.offset(sector, used), &mut record)?;
+
+                // records are appended, the rest of the sector is erased
+                if record.iter().all(|&byte| byte == 0xFF) {
+                    break;
+                }
+                used += 1;
+
+                // a record torn by a power cut still takes its slot
+                if let Some((sequence, value)) = decode(&record) {
+                    if sector_latest.is_none_or(|(latest, _)| sequence > latest) {
+                        sector_latest = Some((sequence, value));
+                    }
+                }
+            }
+
+            if let Some((sequence, value)) = sector_latest {
+                if latest.is_none_or(|(latest, ..)| sequence > latest) {
+                    latest = Some((sequence, value, sector, used));
+                }
+            }
+        }
+
+        if let Some((sequence, value, sector, used)) = latest {
+            self.sequence = sequence + 1;
+            self.sector = sector;
+            self.slot = used;
+            self.value = Some(value);
+        }
+
+        Ok(self.value)
+    }
+
+    /// Appends `value` unless it is already the stored one.
+    pub fn store(&mut self, value: i32) -> Result<(), F::Error> {
+        if self.value == Some(value) {
+            return Ok(());
+        }
+
+        if self.slot == Self::slots_per_sector() {
+            self.sector = (self.sector + 1) % SECTORS;
+            self.slot = 0;
+        }
+        if self.slot == 0 {
+            let start = self.offset(self.sector, 0);
+            self.flash.erase(start, start + Self::sector_size())?;
+        }
+
+        let offset = self.offset(self.sector, self.slot);
+        self.flash.write(offset, &encode(self.sequence, value))?;
+
+        self.slot += 1;
+        self.sequence += 1;
+        self.value = Some(value);
+        Ok(())
+    }
+
+    /// The last value loaded or stored.
+    pub fn value(&self) -> Option<i32> {
+        self.value
+    }
+
+    pub fn release(self) -> F {
+        self.flash
+    }
+
+    fn offset(&self, sector: u32, slot: u32) -> u32 {
+        self.base + sector * Self::sector_size() + slot * RECORD_SIZE
+    }
+
+    fn sector_size() -> u32 {
+        F::ERASE_SIZE as u32
+    }
+
+    fn slots_per_sector() -> u32 {
+        Self::sector_size() / RECORD_SIZE
+    }
+}
+
+/// Tells when a value that stopped changing for `quiet` milliseconds must be
+/// saved, so that a burst of changes costs a single flash write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuietPeriod {
+    quiet: u64,
+    changed_at: Option<u64>,
+}
+
+impl QuietPeriod {
+    pub const fn new(quiet: u64) -> Self {
+        Self {
+            quiet,
+            changed_at: None,
+        }
+    }
+
+    /// Records a change at `now`.
+    pub fn touch(&mut self, now: u64) {
+        self.changed_at = Some(now);
+    }
+
+    /// Whether the value must be saved, only `true` once per change burst.
+    pub fn due(&mut self, now: u64) -> bool {
+        let due = self
+            .changed_at
+            .is_some_and(|changed_at| now.wrapping_sub(changed_at) >= self.quiet);
+        if due {
+            self.changed_at = None;
+        }
+        due
+    }
+}
+
+fn encode(sequence: u32, value: i32) -> [u8; RECORD_SIZE as usize] {
+    let mut record = [0; RECORD_SIZE as usize];
+    record[0..4].copy_from_slice(&sequence.to_le_bytes());
+    record[4..8].copy_from_slice(&value.to_le_bytes());
+    let crc = crc32(&record[0..8]);
+    record[8..12].copy_from_slice(&crc.to_le_bytes());
+    record
+}
+
+fn decode(record: &[u8; RECORD_SIZE as usize]) -> Option<(u32, i32)> {
+    let word = |index: usize| {
+        [
+            record[index],
+            record[index + 1],
+            record[index + 2],
+            record[index + 3],
+        ]
+    };
+
+    let sequence = u32::from_le_bytes(word(0));
+    let value = i32::from_le_bytes(word(4));
+    let crc = u32::from_le_bytes(word(8));
+
+    (sequence != ERASED && crc == crc32(&record[0..8])).then_some((sequence, value))
+}
+
+/// CRC-32 (IEEE), computed bit by bit as records are small.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+
+    use super::*;
+
+    const SECTOR: usize = 64;
+
+    /// Two sectors of RAM behaving like NOR flash, losing power after
+    /// `power_left` written bytes.
+    struct RamFlash {
+        bytes: [u8; 2 * SECTOR],
+        power_left: usize,
+    }
+
+    impl RamFlash {
+        fn new() -> Self {
+            Self {
+                bytes: [0xFF; 2 * SECTOR],
+                power_left: usize::MAX,
+            }
+        }
+    }
+
+    impl ErrorType for RamFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for RamFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.bytes[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.bytes.len()
+        }
+    }
+
+    impl NorFlash for RamFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = SECTOR;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.bytes[from as usize..to as usize].fill(0xFF);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            for (index, &byte) in bytes.iter().enumerate() {
+                if self.power_left == 0 {
+                    return Err(NorFlashErrorKind::Other);
+                }
+                self.power_left -= 1;
+                // programming only clears bits
+                self.bytes[offset as usize + index] &= byte;
+            }
+            Ok(())
+        }
+    }
+
+    fn reload(flash: RamFlash) -> RecordStore<RamFlash> {
+        let mut store = RecordStore::new(flash, 0);
+        store.load().unwrap();
+        store
+    }
+
+    #[test]
+    fn loads_nothing_from_erased_flash() {
+        let mut store = RecordStore::new(RamFlash::new(), 0);
+
+        assert_eq!(store.load(), Ok(None));
+    }
+
+    #[test]
+    fn loads_the_latest_value() {
+        let mut store = RecordStore::new(RamFlash::new(), 0);
+        for value in [3, -7, 42] {
+            store.store(value).unwrap();
+        }
+
+        let store = reload(store.release());
+        assert_eq!(store.value(), Some(42));
+    }
+
+    #[test]
+    fn alternates_sectors() {
+        let mut store = RecordStore::new(RamFlash::new(), 0);
+        // 5 records per sector, the first one erased and reused twice
+        for value in 0..23 {
+            store.store(value).unwrap();
+            store = reload(store.release());
+            assert_eq!(store.value(), Some(value));
+        }
+    }
+
+    #[test]
+    fn skips_storing_the_same_value() {
+        let mut store = RecordStore::new(RamFlash::new(), 0);
+        store.store(1).unwrap();
+        store.store(1).unwrap();
+
+        let mut flash = store.release();
+        assert!(flash.bytes[RECORD_SIZE as usize..]
+            .iter()
+            .all(|&byte| byte == 0xFF));
+        assert_eq!(RecordStore::new(&mut flash, 0).load(), Ok(Some(1)));
+    }
+
+    #[test]
+    fn falls_back_on_the_previous_record_after_a_power_cut() {
+        for torn_bytes in [1, 4, 8, 11] {
+            let mut store = RecordStore::new(RamFlash::new(), 0);
+            store.store(1).unwrap();
+            store.store(2).unwrap();
+
+            let mut flash = store.release();
+            flash.power_left = torn_bytes;
+            let mut store = RecordStore::new(flash, 0);
+            store.load().unwrap();
+            assert!(store.store(3).is_err());
+
+            // the torn record is skipped, the next one goes after it
+            let mut flash = store.release();
+            flash.power_left = usize::MAX;
+            let mut store = reload(flash);
+            assert_eq!(store.value(), Some(2), "{torn_bytes} bytes written");
+
+            store.store(4).unwrap();
+            assert_eq!(reload(store.release()).value(), Some(4));
+        }
+    }
+
+    #[test]
+    fn keeps_the_other_sector_when_tearing_the_first_record_of_a_sector() {
+        let mut store = RecordStore::new(RamFlash::new(), 0);
+        for value in 0..5 {
+            store.store(value).unwrap();
+        }
+
+        // the second sector is erased, then the power goes
+        let mut flash = store.release();
+        flash.power_left = 6;
+        let mut store = reload(flash);
+        assert!(store.store(5).is_err());
+
+        let mut flash = store.release();
+        flash.power_left = usize::MAX;
+        assert_eq!(reload(flash).value(), Some(4));
+    }
+}
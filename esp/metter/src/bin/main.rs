@@ -5,6 +5,13 @@ use core::cell::RefCell;
 use core::fmt;
 
 use critical_section::Mutex;
+use drivers::button::{Button, ButtonConfig, ButtonEvent};
+use drivers::channel::{Channel, Producer};
+use drivers::sonar::filter::{Estimate, Filter, Median, OutlierRejection};
+use drivers::sonar::{
+    send_trigger, Distance, EchoCapture, EchoEvent, EchoTimer, Hcsr04Config, Meter, MeterValue,
+    SonarError,
+};
 use embedded_graphics::mono_font::ascii::FONT_6X10;
 use embedded_graphics::mono_font::iso_8859_1::FONT_10X20;
 use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
@@ -29,9 +36,30 @@ use ssd1306::{I2CDisplayInterface, Ssd1306};
 type StaticPin<T> = Mutex<RefCell<Option<T>>>;
 
 static BUTTON: StaticPin<Input> = Mutex::new(RefCell::new(None));
-static TRIGGER: StaticPin<Output> = Mutex::new(RefCell::new(None));
+static BUTTON_STATE: Mutex<RefCell<Option<Button>>> = Mutex::new(RefCell::new(None));
+static BUTTON_EVENTS: Channel<ButtonEvent, 8> = Channel::new();
 // static DISPLAY_CENTER: Point = ;
 
+const ECHO_EVENTS_CAPACITY: usize = 4;
+// Air temperature in tenths of °C, sets the speed of sound
+const AIR_TEMPERATURE: i16 = 200;
+// Readings filtered into the distance shown on each press, in single mode
+const BURST_SAMPLES: u8 = 5;
+// Readings in the rolling average of the average mode
+const AVERAGE_SAMPLES: usize = 8;
+// Time for the echoes of a measurement to fade out before the next one
+const MEASURE_PERIOD_MS: u64 = 60;
+
+// Echo line of the ultrasonic sensor, timed in the interrupt handler
+struct Echo {
+    pin: Input<'static>,
+    capture: EchoCapture,
+    events: Producer<EchoEvent, ECHO_EVENTS_CAPACITY>,
+}
+
+static ECHO: Mutex<RefCell<Option<Echo>>> = Mutex::new(RefCell::new(None));
+static ECHO_EVENTS: Channel<EchoEvent, ECHO_EVENTS_CAPACITY> = Channel::new();
+
 #[entry]
 fn main() -> ! {
     let peripherals = esp_hal::init({
@@ -48,13 +76,33 @@ fn main() -> ! {
     // Led and button
     let mut led = Output::new(peripherals.GPIO7, Level::Low);
     let mut button = Input::new(peripherals.GPIO6, Pull::Down);
-    button.listen(Event::FallingEdge);
+    button.listen(Event::AnyEdge);
     static_replace(&BUTTON, button);
+    let (producer, mut button_events) = BUTTON_EVENTS.split();
+    critical_section::with(|cs| {
+        BUTTON_STATE
+            .borrow_ref_mut(cs)
+            .replace(Button::new(ButtonConfig::DEFAULT, producer))
+    });
 
     // Ultrasonic sensor
-    let trig = Output::new(peripherals.GPIO1, Level::Low);
-    static_replace(&TRIGGER, trig);
-    let echo = Input::new(peripherals.GPIO0, Pull::None);
+    let mut trig = Output::new(peripherals.GPIO1, Level::Low);
+    let mut echo = Input::new(peripherals.GPIO0, Pull::None);
+    echo.listen(Event::AnyEdge);
+    let (producer, mut echo_events) = ECHO_EVENTS.split();
+    critical_section::with(|cs| {
+        ECHO.borrow_ref_mut(cs).replace(Echo {
+            pin: echo,
+            capture: EchoCapture::new(),
+            events: producer,
+        })
+    });
+    let sonar_config = Hcsr04Config {
+        temperature: AIR_TEMPERATURE,
+        ..Hcsr04Config::DEFAULT
+    };
+    let mut echo_timer = EchoTimer::new(sonar_config, SystemTimer::ticks_per_second() as u32);
+    let mut delay = Delay::new();
 
     // Setup and initialize display
     let i2c = I2c::new(peripherals.I2C0, Config::default())
@@ -86,49 +134,123 @@ fn main() -> ! {
 
     display.flush().unwrap();
 
-    loop {
-        // Wait trigger
-        while !echo.is_high() {}
+    // Drop lost waves and echoes from the floor, then keep the median
+    let mut filter = OutlierRejection::new(50.0, 2).then(Median::<5>::new());
+    let mut meter = Meter::<AVERAGE_SAMPLES>::new(BURST_SAMPLES);
+    let mut long_pressed = false;
+    let mut measured_at = 0;
+    let mut estimate: Option<Estimate> = None;
+    let mut last_error = None;
+    let mut redraw = true;
 
-        // Nice, the wave is sended
-        let echo_start = SystemTimer::now();
-        led.set_high();
-
-        // Wait the wave to get back
-        while !echo.is_low() {}
-
-        // Wave received
-        // Lets proccess to the distance calcul
-
-        led.set_low();
-
-        let echo_end = SystemTimer::now();
+    loop {
+        // A click acts in the current mode, a long press goes to the next one
+        critical_section::with(|cs| {
+            if let Some(button_state) = BUTTON_STATE.borrow_ref_mut(cs).as_mut() {
+                button_state.update(now_ms());
+            }
+        });
+        match button_events.pop() {
+            Some(ButtonEvent::LongPress(_)) => {
+                long_pressed = true;
+                println!("Mode: {}", meter.long_press());
+                filter.reset();
+                last_error = None;
+                redraw = true;
+            }
+            Some(ButtonEvent::Released) if long_pressed => long_pressed = false,
+            Some(ButtonEvent::Released) => {
+                println!("Button clicked");
+                if meter.press() {
+                    filter.reset();
+                    last_error = None;
+                }
+                redraw = true;
+            }
+            _ => {}
+        }
 
-        let echo_duration = echo_end.wrapping_sub(echo_start);
+        if meter.is_measuring()
+            && !echo_timer.is_busy()
+            && now_ms() - measured_at >= MEASURE_PERIOD_MS
+        {
+            measured_at = now_ms();
+            let triggered_at = SystemTimer::now();
+            send_trigger(&mut trig, &mut delay).unwrap();
+            echo_timer.start(triggered_at);
+            led.set_high();
+        }
 
-        let distance_cm = echo_duration / 16 / 58;
+        // The echo edges are timed by the interrupt handler, the loop keeps
+        // going while the wave travels
+        let mut result = None;
+        while let Some(event) = echo_events.pop() {
+            result = result.or(echo_timer.on_event(event));
+        }
+        if let Some(result) = result.or_else(|| echo_timer.check_timeouts(SystemTimer::now())) {
+            led.set_low();
+            redraw = true;
+
+            match result {
+                Ok(distance) => {
+                    println!("Reading: {}", distance);
+                    // Outliers are dropped until the filter gives in
+                    if let Some(filtered) = filter.update(Estimate::from(distance)) {
+                        estimate = Some(filtered);
+                        last_error = None;
+                        meter.update(Some(Distance::from_mm(filtered.value as u32)));
+                    }
+                }
+                Err(error) => {
+                    println!("Reading failed: {:?}", error);
+                    last_error = Some(error);
+                    meter.update(None);
+                }
+            }
+        }
 
-        // Update the distance text for the display
-        if distance_cm > 1000 {
-            distance_text = "I think the\nwave is lost";
-            distance_text_style = base_text_style;
-        } else {
-            distance_text =
-                format_no_std::show(&mut distance_buffer, format_args!("{} cm\n", distance_cm))
-                    .unwrap();
-            distance_text_style = heading_text_style;
+        if !redraw {
+            continue;
         }
+        redraw = false;
 
-        // Draw display
+        // Update the distance text for the display
+        let std_dev = estimate.map_or(0, |estimate| estimate.std_dev());
+        let text = match (meter.value(), last_error) {
+            (MeterValue::Distance(distance), _) => {
+                format_no_std::show(&mut distance_buffer, format_args!("{}\n±{} mm", distance, std_dev))
+            }
+            (MeterValue::Held(distance), _) => {
+                format_no_std::show(&mut distance_buffer, format_args!("{}\nheld", distance))
+            }
+            (MeterValue::Range { min, max }, _) => {
+                format_no_std::show(&mut distance_buffer, format_args!("min {}\nmax {}", min, max))
+            }
+            (MeterValue::Average { mean, samples }, _) => {
+                format_no_std::show(&mut distance_buffer, format_args!("{}\n{} samples", mean, samples))
+            }
+            (MeterValue::Nothing, Some(SonarError::NoEchoStart)) => Ok("Is the sensor\nplugged in?"),
+            (MeterValue::Nothing, Some(_)) => Ok("I think the\nwave is lost"),
+            (MeterValue::Nothing, None) if meter.is_measuring() => Ok("Measuring..."),
+            (MeterValue::Nothing, None) => Ok("Press to\nmeasure"),
+        };
+        distance_text = text.unwrap();
+        distance_text_style = match meter.value() {
+            MeterValue::Nothing => base_text_style,
+            _ => heading_text_style,
+        };
+
+        // Draw display, the mode on the top line
         display.clear_buffer();
 
+        Text::with_baseline(meter.mode().name(), Point::zero(), base_text_style, Baseline::Top)
+            .draw(&mut display)
+            .unwrap();
         center_text(&distance_text, distance_text_style)
             .draw(&mut display)
             .unwrap();
 
         display.flush().unwrap();
-
-        println!("{} cm\n", distance_cm);
     }
 }
 
@@ -136,16 +258,8 @@ fn static_replace<T>(static_pin: &'static StaticPin<T>, pin: T) {
     critical_section::with(|cs| static_pin.borrow_ref_mut(cs).replace(pin));
 }
 
-fn trigger_ultrasonic_sensor() {
-    let delay = Delay::new();
-    critical_section::with(|cs| {
-        let mut binding = TRIGGER.borrow_ref_mut(cs);
-        let trigger = binding.as_mut().unwrap();
-
-        trigger.set_high();
-        delay.delay_micros(10);
-        trigger.set_low();
-    });
+fn now_ms() -> u64 {
+    esp_hal::time::now().duration_since_epoch().to_millis()
 }
 
 fn center_text<'a, S>(text: &'a str, style: S) -> Text<'a, S> {
@@ -157,10 +271,28 @@ fn center_text<'a, S>(text: &'a str, style: S) -> Text<'a, S> {
 #[handler]
 #[ram]
 fn interrupt_handler() {
-    if is_interupt_source(&BUTTON) {
-        println!("Button clicked");
+    // Echo edges first, their timestamp sets the measured distance
+    let now = SystemTimer::now();
+    critical_section::with(|cs| {
+        if let Some(echo) = ECHO.borrow_ref_mut(cs).as_mut() {
+            if echo.pin.is_interrupt_set() {
+                echo.pin.clear_interrupt();
+                if let Some(event) = echo.capture.on_edge(echo.pin.is_high(), now) {
+                    // Counted as dropped when the main loop doesn't keep up
+                    echo.events.push(event).ok();
+                }
+            }
+        }
+    });
 
-        trigger_ultrasonic_sensor();
+    if is_interupt_source(&BUTTON) {
+        let now = now_ms();
+        critical_section::with(|cs| {
+            let pressed = BUTTON.borrow_ref_mut(cs).as_mut().unwrap().is_high();
+            if let Some(button_state) = BUTTON_STATE.borrow_ref_mut(cs).as_mut() {
+                button_state.on_edge(pressed, now);
+            }
+        });
     }
 
     clear_interupt(&BUTTON);
@@ -4,207 +4,39 @@
 use core::cell::RefCell;
 
 use critical_section::Mutex;
-use embedded_hal::digital::OutputPin;
+use drivers::{
+    button::{Button, ButtonConfig, ButtonEvent},
+    channel::Channel,
+    counter::{Counter, CounterCommand, Overflow},
+    seven_segment::{SegmentDisplay, SevenSegmentsLed},
+    storage::{QuietPeriod, RecordStore},
+};
 use esp_backtrace as _;
-use esp_hal::{clock::ClockControl, delay::Delay, gpio::{Event, GpioPin, Input, Output, PullDown, PushPull, IO}, peripherals::Peripherals, prelude::*, riscv::asm::nop};
+use esp_hal::{clock::ClockControl, gpio::{Event, GpioPin, Input, PullDown, IO}, peripherals::Peripherals, prelude::*, systimer::SystemTimer};
 use esp_println::println;
+use esp_storage::FlashStorage;
 
-trait Use {
-    type Error;
-
-    fn zero(&mut self) -> Result<(), Self::Error>;
-    fn one(&mut self) -> Result<(), Self::Error>;
-    fn two(&mut self) -> Result<(), Self::Error>;
-    fn three(&mut self) -> Result<(), Self::Error>;
-    fn four(&mut self) -> Result<(), Self::Error>;
-    fn five(&mut self) -> Result<(), Self::Error>;
-    fn six(&mut self) -> Result<(), Self::Error>;
-    fn seven(&mut self) -> Result<(), Self::Error>;
-    fn height(&mut self) -> Result<(), Self::Error>;
-    fn nine(&mut self) -> Result<(), Self::Error>;
-
-    fn dot(&mut self) -> Result<(), Self::Error>;
-    fn reset(&mut self) -> Result<(), Self::Error>;
-
-    fn display(&mut self, number: u8) -> Result<(), Self::Error> {
-        match number {
-            0 => self.zero(),
-            1 => self.one(),
-            2 => self.two(),
-            3 => self.three(),
-            4 => self.four(),
-            5 => self.five(),
-            6 => self.six(),
-            7 => self.seven(),
-            8 => self.height(),
-            9 => self.nine(),
-            _ => self.reset()
-        }
-    }
-}
-struct SevenSegmentsLed<A, B, C, D, E, F, G, DP, OutputPinError>
-where
-    A: OutputPin<Error = OutputPinError>,
-    B: OutputPin<Error = OutputPinError>,
-    C: OutputPin<Error = OutputPinError>,
-    D: OutputPin<Error = OutputPinError>,
-    E: OutputPin<Error = OutputPinError>,
-    F: OutputPin<Error = OutputPinError>,
-    G: OutputPin<Error = OutputPinError>,
-    DP: OutputPin<Error = OutputPinError>
-{
-    a: A,
-    b: B,
-    c: C,
-    d: D,
-    e: E,
-    f: F,
-    g: G,
-    dp: DP
-}
-
-impl<A, B, C, D, E, F, G, DP, OutputPinError> SevenSegmentsLed<A, B, C, D, E, F, G, DP, OutputPinError>
-where
-    A: OutputPin<Error = OutputPinError>,
-    B: OutputPin<Error = OutputPinError>,
-    C: OutputPin<Error = OutputPinError>,
-    D: OutputPin<Error = OutputPinError>,
-    E: OutputPin<Error = OutputPinError>,
-    F: OutputPin<Error = OutputPinError>,
-    G: OutputPin<Error = OutputPinError>,
-    DP: OutputPin<Error = OutputPinError>
-{
-    fn new(a: A, b: B, c: C, d: D, e: E, f: F, g: G, dp: DP) -> Self {
-        Self {
-            a, b, c, d, e, f, g, dp
-        }
-    }
-}
-
-impl<A, B, C, D, E, F, G, DP, OutputPinError> Use for SevenSegmentsLed<A, B, C, D, E, F, G, DP, OutputPinError>
-where
-    A: OutputPin<Error = OutputPinError>,
-    B: OutputPin<Error = OutputPinError>,
-    C: OutputPin<Error = OutputPinError>,
-    D: OutputPin<Error = OutputPinError>,
-    E: OutputPin<Error = OutputPinError>,
-    F: OutputPin<Error = OutputPinError>,
-    G: OutputPin<Error = OutputPinError>,
-    DP: OutputPin<Error = OutputPinError>
-{
-    type Error = OutputPinError;
-
-    fn zero(&mut self) -> Result<(), Self::Error> {
-        self.a.set_high()?;
-        self.b.set_high()?;
-        self.c.set_high()?;
-        self.d.set_high()?;
-        self.e.set_high()?;
-        self.f.set_high()
-    }
-
-    fn one(&mut self) -> Result<(), Self::Error> {
-        self.b.set_high()?;
-        self.c.set_high()
-    }
-
-    fn two(&mut self) -> Result<(), Self::Error> {
-        self.a.set_high()?;
-        self.b.set_high()?;
-        self.g.set_high()?;
-        self.e.set_high()?;
-        self.d.set_high()
-    }
-
-    fn three(&mut self) -> Result<(), Self::Error> {
-        self.a.set_high()?;
-        self.b.set_high()?;
-        self.g.set_high()?;
-        self.c.set_high()?;
-        self.d.set_high()
-    }
-
-    fn four(&mut self) -> Result<(), Self::Error> {
-        self.f.set_high()?;
-        self.g.set_high()?;
-        self.b.set_high()?;
-        self.c.set_high()
-    }
-
-    fn five(&mut self) -> Result<(), Self::Error> {
-        self.a.set_high()?;
-        self.f.set_high()?;
-        self.g.set_high()?;
-        self.c.set_high()?;
-        self.d.set_high()
-    }
-
-    fn six(&mut self) -> Result<(), Self::Error> {
-        self.a.set_high()?;
-        self.f.set_high()?;
-        self.g.set_high()?;
-        self.e.set_high()?;
-        self.c.set_high()?;
-        self.d.set_high()
-    }
-
-    fn seven(&mut self) -> Result<(), Self::Error> {
-        self.a.set_high()?;
-        self.b.set_high()?;
-        self.c.set_high()
-    }
-
-    fn height(&mut self) -> Result<(), Self::Error> {
-        self.a.set_high()?;
-        self.b.set_high()?;
-        self.c.set_high()?;
-        self.d.set_high()?;
-        self.e.set_high()?;
-        self.f.set_high()?;
-        self.g.set_high()
-    }
-
-    fn nine(&mut self) -> Result<(), Self::Error> {
-        self.a.set_high()?;
-        self.b.set_high()?;
-        self.f.set_high()?;
-        self.g.set_high()?;
-        self.c.set_high()?;
-        self.d.set_high()
-    }
-
-    fn dot(&mut self) -> Result<(), Self::Error> {
-        self.dp.set_high()
-    }
-
-    fn reset(&mut self) -> Result<(), Self::Error> {
-        self.a.set_low()?;
-        self.b.set_low()?;
-        self.c.set_low()?;
-        self.d.set_low()?;
-        self.e.set_low()?;
-        self.f.set_low()?;
-        self.g.set_low()?;
-        self.dp.set_low()
-    }
-}
+// the counter is saved in the nvs partition of the default partition table,
+// unused as long as the firmware doesn't run esp-idf
+const COUNTER_STORAGE_ADDRESS: u32 = 0x9000;
+// how long the counter must stay the same before being saved
+const COUNTER_SAVE_DELAY_MS: u64 = 2000;
 
 static INCREASE_BUTTON: Mutex<RefCell<Option<GpioPin<Input<PullDown>, 8>>>> = Mutex::new(RefCell::new(None));
-static COUNTER: Mutex<RefCell<u8>> = Mutex::new(RefCell::new(0));
+static INCREASE_STATE: Mutex<RefCell<Option<Button>>> = Mutex::new(RefCell::new(None));
+static INCREASE_EVENTS: Channel<ButtonEvent, 8> = Channel::new();
+static RESET_EVENTS: Channel<ButtonEvent, 8> = Channel::new();
 
 #[entry]
 fn main() -> ! {
     let p = Peripherals::take();
     let system = p.SYSTEM.split();
-    let clock = ClockControl::boot_defaults(system.clock_control).freeze();
+    let _clock = ClockControl::boot_defaults(system.clock_control).freeze();
 
     // configure IO
     let mut io = IO::new(p.GPIO, p.IO_MUX);
     io.set_interrupt_handler(increase_handler);
 
-    // configure delay
-    let delay = Delay::new(&clock);
-
     // configure 7 segments LED
     let mut seven_segments = SevenSegmentsLed::new(
         io.pins.gpio0.into_push_pull_output(),
@@ -217,49 +49,94 @@ fn main() -> ! {
         io.pins.gpio7.into_push_pull_output()
     );
 
-    // configure buttons to pins 8 and 9
+    // configure buttons to pins 8 and 9, pressed when high
     let mut increase_button = io.pins.gpio8.into_pull_down_input();
     let reset_button = io.pins.gpio9.into_pull_down_input();
+    let (producer, mut increase_events) = INCREASE_EVENTS.split();
+    let increase_state = Button::new(ButtonConfig::DEFAULT, producer);
+    let (producer, mut reset_events) = RESET_EVENTS.split();
+    let mut reset_state = Button::new(ButtonConfig::DEFAULT, producer);
 
-    increase_button.listen(Event::FallingEdge);
+    increase_button.listen(Event::AnyEdge);
 
     critical_section::with(|cs| {
         INCREASE_BUTTON.borrow_ref_mut(cs).replace(increase_button);
+        INCREASE_STATE.borrow_ref_mut(cs).replace(increase_state);
     });
 
+    // restore the counter, starting over when the flash can't be read
+    let mut storage = RecordStore::new(FlashStorage::new(), COUNTER_STORAGE_ADDRESS);
+    let mut counter = Counter::new(0, 9, Overflow::Saturate);
+    match storage.load() {
+        Ok(Some(value)) => counter = counter.with_value(value),
+        Ok(None) => (),
+        Err(error) => println!("Cannot restore the counter: {:?}", error),
+    }
+    let mut save = QuietPeriod::new(COUNTER_SAVE_DELAY_MS);
+    let mut long_pressed = false;
+
     println!("Coucou");
 
     loop {
-        // get counter value
-        let counter = critical_section::with(|cs| {
-            *COUNTER.borrow(cs).borrow()
-        });
-        println!("Counter: {}", counter);
+        let now = now_ms();
 
-        // display counter
-        seven_segments.reset().unwrap();
-        seven_segments.display(counter).unwrap();
+        // check increase button
+        critical_section::with(|cs| {
+            if let Some(increase_state) = INCREASE_STATE.borrow_ref_mut(cs).as_mut() {
+                increase_state.update(now);
+            }
+        });
+        // a click increments once released, a long press decrements instead
+        let increase_command = match increase_events.pop() {
+            Some(ButtonEvent::LongPress(_)) => {
+                long_pressed = true;
+                Some(CounterCommand::Decrement)
+            }
+            Some(ButtonEvent::Released) if long_pressed => {
+                long_pressed = false;
+                None
+            }
+            Some(ButtonEvent::Released) => Some(CounterCommand::Increment),
+            _ => None,
+        };
 
         // check reset button
-        if reset_button.is_low() {
-            println!("Reset");
-            critical_section::with(|cs| {
-                *COUNTER.borrow(cs).borrow_mut() = 0;
-            });
+        reset_state.poll(reset_button.is_high(), now);
+        let reset_command = match reset_events.pop() {
+            Some(ButtonEvent::Pressed) => Some(CounterCommand::Reset),
+            _ => None,
+        };
+
+        for command in increase_command.into_iter().chain(reset_command) {
+            if counter.apply(command) {
+                println!("{:?}, counter: {}", command, counter.value());
+                save.touch(now);
+            }
+        }
+
+        // save counter once it stopped changing
+        if save.due(now) {
+            storage.store(counter.value()).unwrap();
         }
+
+        // display counter
+        seven_segments.display(counter.value() as u8).unwrap();
     }
 }
 
+fn now_ms() -> u64 {
+    SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000)
+}
+
 #[handler]
 fn increase_handler() {
+    let now = now_ms();
     critical_section::with(|cs| {
-        if let Some(increate_button) = INCREASE_BUTTON.borrow(cs).borrow_mut().as_mut() {
-            println!("Interrupt {}", critical_section::with(|cs| *COUNTER.borrow(cs).borrow()));
-            increate_button.clear_interrupt();
-
-            if *COUNTER.borrow(cs).borrow() < 9 {
-                *COUNTER.borrow(cs).borrow_mut() += 1;
+        if let Some(increase_button) = INCREASE_BUTTON.borrow_ref_mut(cs).as_mut() {
+            increase_button.clear_interrupt();
+            if let Some(increase_state) = INCREASE_STATE.borrow_ref_mut(cs).as_mut() {
+                increase_state.on_edge(increase_button.is_high(), now);
             }
         }
     });
-}
\ No newline at end of file
+}
@@ -0,0 +1,90 @@
+#![no_std]
+#![no_main]
+
+use drivers::seven_segment::{
+    brightness::{AutoBrightness, Dimmer, MAX_LEVEL},
+    Polarity, SegmentDisplay, SevenSegmentsLed,
+};
+use esp_backtrace as _;
+use esp_hal as hal;
+use esp_println::println;
+use hal::{
+    analog::adc::{AdcCalLine, AdcConfig, Attenuation, ADC},
+    clock::ClockControl,
+    delay::Delay,
+    gpio::IO,
+    ledc::{channel, timer, LSGlobalClkSource, LowSpeed, LEDC},
+    peripherals::{Peripherals, ADC1},
+    prelude::*,
+};
+
+// raw readings of the light sensor divider in the dark and in daylight
+const LIGHT_DARK: u16 = 100;
+const LIGHT_BRIGHT: u16 = 2500;
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take();
+    let system = p.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let io = IO::new(p.GPIO, p.IO_MUX);
+    let delay = Delay::new(&clocks);
+
+    // configure 7 segments LED on pins 2 to 9
+    let mut seven_segments = SevenSegmentsLed::new(
+        io.pins.gpio2.into_push_pull_output(),
+        io.pins.gpio3.into_push_pull_output(),
+        io.pins.gpio4.into_push_pull_output(),
+        io.pins.gpio5.into_push_pull_output(),
+        io.pins.gpio6.into_push_pull_output(),
+        io.pins.gpio7.into_push_pull_output(),
+        io.pins.gpio8.into_push_pull_output(),
+        io.pins.gpio9.into_push_pull_output(),
+    );
+
+    // setup LED PWM Controller for the common cathode on pin 10, switched by an
+    // NPN transistor
+    let mut ledc = LEDC::new(p.LEDC, &clocks);
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+
+    let common = io.pins.gpio10.into_push_pull_output();
+
+    let mut lstimer = ledc.get_timer::<LowSpeed>(timer::Number::Timer0);
+    lstimer.configure(timer::config::Config {
+        duty: timer::config::Duty::Duty14Bit,
+        clock_source: timer::LSClockSource::APBClk,
+        frequency: 1.kHz()
+    }).unwrap();
+
+    let mut channel0 = ledc.get_channel(channel::Number::Channel0, common);
+    channel0.configure(channel::config::Config {
+        timer: &lstimer,
+        duty_pct: 100,
+        pin_config: channel::config::PinConfig::PushPull
+    }).unwrap();
+
+    let mut dimmer = Dimmer::new(channel0, Polarity::ActiveHigh);
+
+    // create ADC instance to read the light sensor on pin 0
+    let mut adc_config = AdcConfig::<ADC1>::new();
+    let analog_pin0 = io.pins.gpio0.into_analog();
+    let mut light_sensor = adc_config.enable_pin_with_cal::<_, AdcCalLine<ADC1>>(analog_pin0, Attenuation::Attenuation11dB);
+    let mut adc = ADC::new(p.ADC1, adc_config);
+
+    let mut auto_brightness = AutoBrightness::new(LIGHT_DARK, LIGHT_BRIGHT, 1, MAX_LEVEL);
+
+    seven_segments.display_char('8').unwrap();
+
+    loop {
+        let light: u16 = nb::block!(adc.read_oneshot(&mut light_sensor)).unwrap();
+        let level = auto_brightness.update(light);
+
+        if level != dimmer.level() {
+            println!("Light : {} | Brightness : {}", light, level);
+            dimmer.set_level(level).unwrap();
+        }
+
+        delay.delay_millis(50);
+    }
+}
@@ -1,10 +1,19 @@
 #![no_std]
 #![no_main]
 
-use core::cell::RefCell;
+use core::{
+    cell::{Cell, RefCell},
+    fmt::Write,
+};
 
 use critical_section::Mutex;
 use critical_section::CriticalSection;
+use drivers::{
+    button::{Button, ButtonConfig, ButtonEvent},
+    channel::Channel,
+    command::{self, Command, LineBuffer, Watchdog},
+    motor::{DifferentialDrive, DriveConfig, DriveInput, HBridge, Ramp, RampConfig},
+};
 use esp_backtrace as _;
 use esp_hal as hal;
 use esp_println::println;
@@ -17,29 +26,35 @@ use hal::{
         AnyPin, Event, Floating, Gpio8, Gpio9, GpioPin, Input, Output, PullDown, PullUp, PushPull,
         IO,
     },
-    ledc::channel::config::PinConfig,
-    peripherals::{self, Peripherals},
+    ledc::{channel, timer, LSGlobalClkSource, LowSpeed, LEDC},
+    peripherals::{self, Peripherals, TIMG0},
     prelude::*,
     riscv::{asm::nop, interrupt},
+    systimer::SystemTimer,
+    timer::{Timer, Timer0, TimerGroup},
+    usb_serial_jtag::UsbSerialJtag,
+    Blocking,
 };
 
-// Motor
-struct Motor<const GPIONUM_A: u8, const GPIONUM_B: u8> {
-    clockwise_pin: GpioPin<Output<PushPull>, GPIONUM_A>,
-    anti_clockwise_pin: GpioPin<Output<PushPull>, GPIONUM_B>,
-}
+// speeds move towards their targets every 10 ms, full speed reached in half a
+// second so that starting both motors doesn't brown out the board
+const RAMP_PERIOD_MS: u32 = 10;
+const RAMP: RampConfig = RampConfig {
+    acceleration: 200,
+    deceleration: 400,
+};
 
-impl<const GPIONUM_A: u8, const GPIONUM_B: u8> Motor<GPIONUM_A, GPIONUM_B> {
-    fn new(
-        pin_a: GpioPin<Output<PushPull>, GPIONUM_A>,
-        pin_b: GpioPin<Output<PushPull>, GPIONUM_B>,
-    ) -> Self {
-        Self {
-            clockwise_pin: pin_a,
-            anti_clockwise_pin: pin_b,
-        }
-    }
-}
+// motors driven over serial stop when no command came for this long
+const COMMAND_TIMEOUT_MS: u64 = 1000;
+
+static RAMP_TIMER: Mutex<RefCell<Option<Timer<Timer0<TIMG0>, Blocking>>>> =
+    Mutex::new(RefCell::new(None));
+// ramp periods elapsed and not yet applied by the main loop
+static RAMP_TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+// events of the buttons, unused as only their level matters
+static BUTTON1_EVENTS: Channel<ButtonEvent, 8> = Channel::new();
+static BUTTON2_EVENTS: Channel<ButtonEvent, 8> = Channel::new();
 
 // static BUTTON1: Mutex<RefCell<Option<Gpio8<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
 // static BUTTON2: Mutex<RefCell<Option<Gpio9<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
@@ -56,36 +71,174 @@ fn main() -> ! {
     // configure delay
     let delay = Delay::new(&clock);
 
-    // configure motor 1
-    let mut motor1 = Motor::new(
-        io.pins.gpio6.into_push_pull_output(),
-        io.pins.gpio7.into_push_pull_output(),
-    );
+    // setup LED PWM Controller for the motor inputs, above hearing range
+    let mut ledc = LEDC::new(p.LEDC, &clock);
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+
+    let mut lstimer = ledc.get_timer::<LowSpeed>(timer::Number::Timer0);
+    lstimer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty10Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: 20.kHz(),
+        })
+        .unwrap();
+
+    let mut channels = [
+        ledc.get_channel(
+            channel::Number::Channel0,
+            io.pins.gpio6.into_push_pull_output().degrade(),
+        ),
+        ledc.get_channel(
+            channel::Number::Channel1,
+            io.pins.gpio7.into_push_pull_output().degrade(),
+        ),
+        ledc.get_channel(
+            channel::Number::Channel2,
+            io.pins.gpio4.into_push_pull_output().degrade(),
+        ),
+        ledc.get_channel(
+            channel::Number::Channel3,
+            io.pins.gpio5.into_push_pull_output().degrade(),
+        ),
+    ];
+    for motor_input in channels.iter_mut() {
+        motor_input
+            .configure(channel::config::Config {
+                timer: &lstimer,
+                duty_pct: 0,
+                pin_config: channel::config::PinConfig::PushPull,
+            })
+            .unwrap();
+    }
+    let [motor1_a, motor1_b, motor2_a, motor2_b] = channels;
+
+    // configure motor 1, the left wheel, mounted the other way round
+    let mut motor1 = HBridge::new(motor1_a, motor1_b).unwrap();
+
+    // configure motor 2, the right wheel
+    let mut motor2 = HBridge::new(motor2_a, motor2_b).unwrap();
 
-    // configure motor 2
-    let mut motor2 = Motor::new(
-        io.pins.gpio4.into_push_pull_output(),
-        io.pins.gpio5.into_push_pull_output(),
-    );
+    let drive = DifferentialDrive::new(DriveConfig {
+        invert_left: true,
+        ..DriveConfig::DEFAULT
+    });
+
+    let mut ramps = [Ramp::new(RAMP), Ramp::new(RAMP)];
+
+    // configure the ramp timer
+    let timg0 = TimerGroup::new(p.TIMG0, &clock, None);
+    let mut timer0 = timg0.timer0;
+    timer0.start((RAMP_PERIOD_MS as u64).millis());
+    timer0.listen();
+
+    critical_section::with(|cs| {
+        RAMP_TIMER.borrow_ref_mut(cs).replace(timer0);
+    });
+
+    hal::interrupt::enable(Interrupt::TG0_T0_LEVEL, Priority::Priority1).unwrap();
 
     // configure buttons for motors
     let button1 = io.pins.gpio8.into_pull_up_input();
     let button2 = io.pins.gpio9.into_pull_up_input();
+    let mut button1_state = Button::new(ButtonConfig::DEFAULT, BUTTON1_EVENTS.split().0);
+    let mut button2_state = Button::new(ButtonConfig::DEFAULT, BUTTON2_EVENTS.split().0);
+
+    // configure the serial console for remote commands, e.g. `M1 -40`
+    let mut serial = UsbSerialJtag::new(p.USB_DEVICE);
+    let mut line: LineBuffer = LineBuffer::new();
+    let mut watchdog = Watchdog::new(COMMAND_TIMEOUT_MS);
+    // speed of each motor asked over serial
+    let mut remote: Option<[i8; 2]> = None;
+    // motors held braked after STOP until the next command
+    let mut braking = false;
 
     loop {
-        if button1.is_low() {
-            println!("Right");
-            motor1.anti_clockwise_pin.set_high();
-        } else {
-            motor1.anti_clockwise_pin.set_low();
+        let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+        button1_state.poll(button1.is_low(), now);
+        button2_state.poll(button2.is_low(), now);
+
+        while let Ok(byte) = serial.read_byte() {
+            let Some(received) = line.push(byte) else {
+                continue;
+            };
+
+            match received.and_then(command::parse) {
+                Ok(Command::Motor { motor, speed }) => {
+                    let mut speeds = remote.unwrap_or([0; 2]);
+                    speeds[motor as usize] = speed;
+                    remote = Some(speeds);
+                    braking = false;
+                    watchdog.feed(now);
+                    writeln!(serial, "OK").ok();
+                }
+                Ok(Command::Drive(input)) => {
+                    let wheels = drive.mix(input);
+                    remote = Some([wheels.left, wheels.right]);
+                    braking = false;
+                    watchdog.feed(now);
+                    writeln!(serial, "OK").ok();
+                }
+                Ok(Command::Stop) => {
+                    remote = None;
+                    braking = true;
+                    watchdog.disarm();
+                    ramps.iter_mut().for_each(Ramp::emergency_stop);
+                    motor1.brake().unwrap();
+                    motor2.brake().unwrap();
+                    writeln!(serial, "OK").ok();
+                }
+                Ok(Command::Status) => {
+                    writeln!(serial, "M1 {} M2 {}", ramps[0].speed(), ramps[1].speed()).ok();
+                }
+                Err(error) => {
+                    writeln!(serial, "{}", error).ok();
+                }
+            }
+        }
+
+        if watchdog.expired(now) {
+            remote = None;
+            ramps.iter_mut().for_each(Ramp::emergency_stop);
+            writeln!(serial, "ERR command timeout, motors stopped").ok();
         }
-        if button2.is_low() {
-            println!("Left");
-            motor2.clockwise_pin.set_high();
+
+        // the buttons take over while pressed, one turns towards the other
+        // side, both drive straight
+        let targets = if button1_state.is_pressed() || button2_state.is_pressed() {
+            braking = false;
+            let input = DriveInput::from_buttons(
+                button1_state.is_pressed(),
+                button2_state.is_pressed(),
+                100,
+            );
+            let wheels = drive.mix(input);
+            [wheels.left, wheels.right]
         } else {
-            motor2.clockwise_pin.set_low();
+            remote.unwrap_or([0; 2])
+        };
+        ramps[0].set_target(targets[0]);
+        ramps[1].set_target(targets[1]);
+
+        let ticks = critical_section::with(|cs| RAMP_TICKS.borrow(cs).replace(0));
+        // setting the speed would let braked motors coast
+        if ticks > 0 && !braking {
+            let elapsed_ms = ticks * RAMP_PERIOD_MS;
+            motor1.set_speed(ramps[0].update(elapsed_ms)).unwrap();
+            motor2.set_speed(ramps[1].update(elapsed_ms)).unwrap();
         }
     }
 }
 
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    critical_section::with(|cs| {
+        let ticks = RAMP_TICKS.borrow(cs);
+        ticks.set(ticks.get() + 1);
 
+        if let Some(timer0) = RAMP_TIMER.borrow_ref_mut(cs).as_mut() {
+            timer0.clear_interrupt();
+            timer0.start((RAMP_PERIOD_MS as u64).millis());
+        }
+    });
+}
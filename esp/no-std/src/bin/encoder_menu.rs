@@ -0,0 +1,164 @@
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use drivers::{
+    button::{Button, ButtonConfig, ButtonEvent},
+    channel::{Channel, Producer},
+    encoder::{QuadratureDecoder, StepsPerDetent, TurnEvent},
+    menu::{self, Input, Item, Menu, MenuEvent},
+};
+use esp_backtrace as _;
+use esp_hal::{
+    clock::ClockControl,
+    gpio::{AnyPin, Event, Floating, Input as InputPin, PullUp, IO},
+    i2c::I2C,
+    peripherals::Peripherals,
+    prelude::*,
+    systimer::SystemTimer,
+};
+use esp_println::println;
+use ssd1306::{prelude::*, size::DisplaySize128x64, I2CDisplayInterface, Ssd1306};
+
+const EVENTS_CAPACITY: usize = 32;
+const BUTTON_EVENTS_CAPACITY: usize = 8;
+
+// menu values
+const SPEED: usize = 0;
+const BEEP: usize = 1;
+const UNIT: usize = 2;
+const CONTRAST: usize = 3;
+const RESET: usize = 4;
+
+const DISPLAY_MENU: &[Item] = &[
+    Item::Number { label: "Contrast", id: CONTRAST, min: 0, max: 255, step: 15 },
+    Item::Choice { label: "Unit", id: UNIT, options: &["cm", "inch"] },
+];
+
+const MAIN_MENU: &[Item] = &[
+    Item::Number { label: "Speed", id: SPEED, min: 0, max: 100, step: 5 },
+    Item::Toggle { label: "Beep", id: BEEP },
+    Item::Submenu { label: "Display", items: DISPLAY_MENU },
+    Item::Action { label: "Reset", id: RESET },
+];
+
+const DEFAULT_VALUES: [i32; 5] = [50, 1, 0, 255, 0];
+
+struct Inputs {
+    clock: AnyPin<InputPin<Floating>>,
+    data: AnyPin<InputPin<Floating>>,
+    switch: AnyPin<InputPin<PullUp>>,
+    decoder: QuadratureDecoder,
+    turns: Producer<TurnEvent, EVENTS_CAPACITY>,
+    button: Button<BUTTON_EVENTS_CAPACITY>,
+}
+
+static INPUTS: Mutex<RefCell<Option<Inputs>>> = Mutex::new(RefCell::new(None));
+static TURNS: Channel<TurnEvent, EVENTS_CAPACITY> = Channel::new();
+static BUTTON_EVENTS: Channel<ButtonEvent, BUTTON_EVENTS_CAPACITY> = Channel::new();
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take();
+    let system = p.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    // configure IO
+    let mut io = IO::new(p.GPIO, p.IO_MUX);
+    io.set_interrupt_handler(inputs_interrupt);
+
+    // configure i2c and the display
+    let i2c = I2C::new(p.I2C0, io.pins.gpio0, io.pins.gpio1, 100.kHz(), &clocks, None);
+    let interface = I2CDisplayInterface::new(i2c);
+    let mut display = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode();
+    display.init().unwrap();
+
+    // configure rotary encoder and its switch, pressed when low
+    let mut clk_pin = io.pins.gpio2.into_floating_input();
+    let mut dt_pin = io.pins.gpio3.into_floating_input();
+    let mut sw_pin = io.pins.gpio4.into_pull_up_input();
+    clk_pin.listen(Event::AnyEdge);
+    dt_pin.listen(Event::AnyEdge);
+    sw_pin.listen(Event::AnyEdge);
+
+    let (producer, mut turns) = TURNS.split();
+    let (button_producer, mut button_events) = BUTTON_EVENTS.split();
+    let inputs = Inputs {
+        decoder: QuadratureDecoder::new(StepsPerDetent::Four, clk_pin.is_high(), dt_pin.is_high()),
+        clock: clk_pin.degrade().into(),
+        data: dt_pin.degrade().into(),
+        switch: sw_pin.degrade().into(),
+        turns: producer,
+        button: Button::new(ButtonConfig::DEFAULT, button_producer),
+    };
+
+    critical_section::with(|cs| {
+        INPUTS.borrow_ref_mut(cs).replace(inputs);
+    });
+
+    let mut menu: Menu<5> = Menu::new("Settings", MAIN_MENU).with_values(DEFAULT_VALUES);
+    let mut redraw = true;
+
+    loop {
+        let mut events = [None; 2];
+
+        if let Some(turn) = turns.pop() {
+            events[0] = menu.handle(Input::from(turn));
+            redraw = true;
+        }
+
+        critical_section::with(|cs| {
+            if let Some(inputs) = INPUTS.borrow_ref_mut(cs).as_mut() {
+                inputs.button.update(now_ms());
+            }
+        });
+        if let Some(button_event) = button_events.pop() {
+            events[1] = menu.handle_button(button_event);
+            redraw = true;
+        }
+
+        for event in events.into_iter().flatten() {
+            match event {
+                MenuEvent::Changed { id: CONTRAST, value } => {
+                    display.set_brightness(Brightness::custom(1, value as u8)).unwrap();
+                }
+                MenuEvent::Changed { id, value } => println!("Value {} changed to {}", id, value),
+                MenuEvent::Action { id: RESET, .. } => {
+                    menu = Menu::new("Settings", MAIN_MENU).with_values(DEFAULT_VALUES);
+                }
+                MenuEvent::Action { .. } => (),
+                MenuEvent::Exited => println!("Exited menu"),
+            }
+        }
+
+        if redraw {
+            menu::render(&menu, &mut display).unwrap();
+            display.flush().unwrap();
+            redraw = false;
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000)
+}
+
+#[handler]
+fn inputs_interrupt() {
+    let now = now_ms();
+    critical_section::with(|cs| {
+        if let Some(inputs) = INPUTS.borrow_ref_mut(cs).as_mut() {
+            inputs.clock.clear_interrupt();
+            inputs.data.clear_interrupt();
+            inputs.switch.clear_interrupt();
+
+            if let Some(turn) = inputs.decoder.update(inputs.clock.is_high(), inputs.data.is_high()) {
+                inputs.turns.push(TurnEvent { turn, at: now }).ok();
+            }
+            inputs.button.on_edge(inputs.switch.is_low(), now);
+        }
+    });
+}
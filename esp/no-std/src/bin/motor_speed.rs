@@ -0,0 +1,183 @@
+#![no_std]
+#![no_main]
+
+use core::cell::{Cell, RefCell};
+
+use critical_section::Mutex;
+use drivers::{
+    encoder::{QuadratureDecoder, StepsPerDetent},
+    motor::{HBridge, PidConfig, SpeedController},
+};
+use esp_backtrace as _;
+use esp_hal as hal;
+use esp_println::println;
+use hal::{
+    clock::ClockControl,
+    gpio::{AnyPin, Event, Floating, Input, IO},
+    interrupt::{self, Priority},
+    ledc::{channel, timer, LSGlobalClkSource, LowSpeed, LEDC},
+    peripherals::{Interrupt, Peripherals, TIMG0},
+    prelude::*,
+    timer::{Timer, Timer0, TimerGroup},
+    Blocking,
+};
+
+// gear motor with a 7 pulses encoder on its shaft and a 1:30 gearbox, every
+// edge of both lines counted
+const COUNTS_PER_REVOLUTION: u32 = 7 * 4 * 30;
+const SAMPLE_PERIOD_MS: u32 = 20;
+const TARGET_RPM: f32 = 120.0;
+const PID: PidConfig = PidConfig::DEFAULT.with_gains(0.2, 2.0, 0.0);
+// speed printed every second
+const REPORT_SAMPLES: u32 = 1000 / SAMPLE_PERIOD_MS;
+
+struct WheelEncoder {
+    a: AnyPin<Input<Floating>>,
+    b: AnyPin<Input<Floating>>,
+    decoder: QuadratureDecoder,
+}
+
+impl WheelEncoder {
+    fn on_edge(&mut self) {
+        self.a.clear_interrupt();
+        self.b.clear_interrupt();
+
+        self.decoder.update(self.a.is_high(), self.b.is_high());
+    }
+}
+
+static WHEEL_ENCODER: Mutex<RefCell<Option<WheelEncoder>>> = Mutex::new(RefCell::new(None));
+static SAMPLE_TIMER: Mutex<RefCell<Option<Timer<Timer0<TIMG0>, Blocking>>>> =
+    Mutex::new(RefCell::new(None));
+// sample periods elapsed and not yet handled by the main loop
+static SAMPLE_TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take();
+    let system = p.SYSTEM.split();
+    let clock = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    // configure IO
+    let mut io = IO::new(p.GPIO, p.IO_MUX);
+    io.set_interrupt_handler(encoder_interrupt);
+
+    // configure the wheel encoder
+    let mut a_pin = io.pins.gpio2.into_floating_input();
+    let mut b_pin = io.pins.gpio3.into_floating_input();
+    a_pin.listen(Event::AnyEdge);
+    b_pin.listen(Event::AnyEdge);
+
+    let encoder = WheelEncoder {
+        decoder: QuadratureDecoder::new(StepsPerDetent::One, a_pin.is_high(), b_pin.is_high()),
+        a: a_pin.degrade().into(),
+        b: b_pin.degrade().into(),
+    };
+
+    critical_section::with(|cs| {
+        WHEEL_ENCODER.borrow_ref_mut(cs).replace(encoder);
+    });
+
+    // setup LED PWM Controller for the motor inputs, above hearing range
+    let mut ledc = LEDC::new(p.LEDC, &clock);
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+
+    let mut lstimer = ledc.get_timer::<LowSpeed>(timer::Number::Timer0);
+    lstimer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty10Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: 20.kHz(),
+        })
+        .unwrap();
+
+    let mut channels = [
+        ledc.get_channel(
+            channel::Number::Channel0,
+            io.pins.gpio6.into_push_pull_output().degrade(),
+        ),
+        ledc.get_channel(
+            channel::Number::Channel1,
+            io.pins.gpio7.into_push_pull_output().degrade(),
+        ),
+    ];
+    for motor_input in channels.iter_mut() {
+        motor_input
+            .configure(channel::config::Config {
+                timer: &lstimer,
+                duty_pct: 0,
+                pin_config: channel::config::PinConfig::PushPull,
+            })
+            .unwrap();
+    }
+    let [forward, reverse] = channels;
+    let mut motor = HBridge::new(forward, reverse).unwrap();
+
+    // configure the sample timer
+    let timg0 = TimerGroup::new(p.TIMG0, &clock, None);
+    let mut timer0 = timg0.timer0;
+    timer0.start((SAMPLE_PERIOD_MS as u64).millis());
+    timer0.listen();
+
+    critical_section::with(|cs| {
+        SAMPLE_TIMER.borrow_ref_mut(cs).replace(timer0);
+    });
+
+    interrupt::enable(Interrupt::TG0_T0_LEVEL, Priority::Priority1).unwrap();
+
+    let mut controller = SpeedController::new(COUNTS_PER_REVOLUTION, SAMPLE_PERIOD_MS, PID);
+    controller.set_target(TARGET_RPM);
+    let mut samples: u32 = 0;
+
+    loop {
+        let ticks = critical_section::with(|cs| SAMPLE_TICKS.borrow(cs).replace(0));
+        if ticks == 0 {
+            continue;
+        }
+        if ticks > 1 {
+            println!("Missed {} samples", ticks - 1);
+        }
+
+        let position = critical_section::with(|cs| {
+            WHEEL_ENCODER
+                .borrow_ref(cs)
+                .as_ref()
+                .map_or(0, |encoder| encoder.decoder.position())
+        });
+        // the speed is measured over all the periods since the last sample
+        let speed = controller.update_after(position, ticks * SAMPLE_PERIOD_MS);
+        motor.set_speed(speed).unwrap();
+
+        samples += 1;
+        if samples % REPORT_SAMPLES == 0 {
+            println!(
+                "Target: {} rpm | Speed: {} rpm | PWM: {}%",
+                controller.target(),
+                controller.rpm(),
+                speed
+            );
+        }
+    }
+}
+
+#[handler]
+fn encoder_interrupt() {
+    critical_section::with(|cs| {
+        if let Some(encoder) = WHEEL_ENCODER.borrow_ref_mut(cs).as_mut() {
+            encoder.on_edge();
+        }
+    });
+}
+
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    critical_section::with(|cs| {
+        let ticks = SAMPLE_TICKS.borrow(cs);
+        ticks.set(ticks.get() + 1);
+
+        if let Some(timer0) = SAMPLE_TIMER.borrow_ref_mut(cs).as_mut() {
+            timer0.clear_interrupt();
+            timer0.start((SAMPLE_PERIOD_MS as u64).millis());
+        }
+    });
+}
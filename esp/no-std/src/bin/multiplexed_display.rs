@@ -0,0 +1,135 @@
+#![no_std]
+#![no_main]
+
+use core::{cell::RefCell, convert::Infallible};
+
+use critical_section::Mutex;
+use drivers::seven_segment::{Marquee, MultiDigitDisplay, Multiplexed, SevenSegmentsLed};
+use esp_backtrace as _;
+use esp_hal::{
+    clock::ClockControl,
+    gpio::{AnyPin, Output, PushPull, IO},
+    interrupt::{self, Priority},
+    peripherals::{Interrupt, Peripherals, TIMG0},
+    prelude::*,
+    systimer::SystemTimer,
+    timer::{Timer, Timer0, TimerGroup},
+    Blocking,
+};
+use esp_println::println;
+
+type Pin = AnyPin<Output<PushPull>>;
+type Display = Multiplexed<SevenSegmentsLed<Pin, Pin, Pin, Pin, Pin, Pin, Pin, Pin, Infallible>, Pin, 4>;
+
+// 4 digits refreshed at 125 Hz each
+const REFRESH_SLOT_US: u32 = 2000;
+const BRIGHTNESS: u8 = 10;
+const GREETING_MS: u64 = 6000;
+const COUNT_PERIOD_MS: u64 = 100;
+const COUNT_END: i32 = 50;
+
+static DISPLAY: Mutex<RefCell<Option<Display>>> = Mutex::new(RefCell::new(None));
+static REFRESH_TIMER: Mutex<RefCell<Option<Timer<Timer0<TIMG0>, Blocking>>>> = Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take();
+    let system = p.SYSTEM.split();
+    let clock = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    // configure IO
+    let io = IO::new(p.GPIO, p.IO_MUX);
+
+    // configure the 4 digits display, segments on pins 0 to 7
+    let segments = SevenSegmentsLed::new(
+        io.pins.gpio0.into_push_pull_output().degrade(),
+        io.pins.gpio1.into_push_pull_output().degrade(),
+        io.pins.gpio2.into_push_pull_output().degrade(),
+        io.pins.gpio3.into_push_pull_output().degrade(),
+        io.pins.gpio4.into_push_pull_output().degrade(),
+        io.pins.gpio5.into_push_pull_output().degrade(),
+        io.pins.gpio6.into_push_pull_output().degrade(),
+        io.pins.gpio7.into_push_pull_output().degrade(),
+    );
+    let digits = [
+        io.pins.gpio8.into_push_pull_output().degrade(),
+        io.pins.gpio9.into_push_pull_output().degrade(),
+        io.pins.gpio10.into_push_pull_output().degrade(),
+        io.pins.gpio20.into_push_pull_output().degrade(),
+    ];
+    let mut display = Multiplexed::new(segments, digits);
+    display.clear().unwrap();
+    display.set_brightness(BRIGHTNESS);
+
+    critical_section::with(|cs| {
+        DISPLAY.borrow_ref_mut(cs).replace(display);
+    });
+
+    // configure the refresh timer
+    let timg0 = TimerGroup::new(p.TIMG0, &clock, None);
+    let mut timer0 = timg0.timer0;
+    timer0.start((REFRESH_SLOT_US as u64).micros());
+    timer0.listen();
+
+    critical_section::with(|cs| {
+        REFRESH_TIMER.borrow_ref_mut(cs).replace(timer0);
+    });
+
+    interrupt::enable(Interrupt::TG0_T0_LEVEL, Priority::Priority1).unwrap();
+
+    // scroll a greeting, count and blink when done
+    let mut marquee: Marquee<4> = Marquee::new(300, 250);
+    marquee.set_text("HELLo 7 SEGMEntS", now_ms());
+
+    let mut counter: i32 = -20;
+    let mut counted_at = now_ms();
+
+    loop {
+        let now = now_ms();
+
+        if now < GREETING_MS || counter == COUNT_END {
+            let masks = marquee.frame(now);
+            with_display(|display| display.write_masks(masks).unwrap());
+            continue;
+        }
+
+        if now - counted_at >= COUNT_PERIOD_MS {
+            counted_at = now;
+            counter += 1;
+            println!("Counter: {}", counter);
+            with_display(|display| display.set_number(counter, None).unwrap());
+
+            if counter == COUNT_END {
+                marquee.set_text("donE", now);
+                marquee.set_blink_all(true);
+            }
+        }
+    }
+}
+
+fn with_display(f: impl FnOnce(&mut Display)) {
+    critical_section::with(|cs| {
+        if let Some(display) = DISPLAY.borrow_ref_mut(cs).as_mut() {
+            f(display);
+        }
+    });
+}
+
+fn now_ms() -> u64 {
+    SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000)
+}
+
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    critical_section::with(|cs| {
+        let mut wait_us = REFRESH_SLOT_US;
+        if let Some(display) = DISPLAY.borrow_ref_mut(cs).as_mut() {
+            wait_us = display.tick_dimmed(REFRESH_SLOT_US).unwrap();
+        }
+
+        if let Some(timer0) = REFRESH_TIMER.borrow_ref_mut(cs).as_mut() {
+            timer0.clear_interrupt();
+            timer0.start((wait_us as u64).micros());
+        }
+    });
+}
@@ -4,6 +4,10 @@
 use core::{borrow::{Borrow, BorrowMut}, cell::RefCell};
 
 use critical_section::Mutex;
+use drivers::{
+    channel::{Channel, Producer},
+    encoder::{Acceleration, QuadratureDecoder, StepsPerDetent, TurnEvent},
+};
 use esp_backtrace as _;
 use esp_hal as hal;
 use esp_println::println;
@@ -15,45 +19,49 @@ use hal::{
     },
     peripherals::Peripherals,
     prelude::*,
+    systimer::SystemTimer,
 };
 use log::Level;
 
 // Rotary encoder
-#[derive(PartialEq, Debug)]
-enum Turn {
-    Left,
-    Right,
-}
+const EVENTS_CAPACITY: usize = 32;
 
 struct RotaryEncoder {
     clock: AnyPin<Input<Floating>>,
     data: AnyPin<Input<Floating>>,
-    turn: Option<Turn>,
+    decoder: QuadratureDecoder,
+    events: Producer<TurnEvent, EVENTS_CAPACITY>,
 }
 
 impl RotaryEncoder {
-    fn new(clock: AnyPin<Input<Floating>>, data: AnyPin<Input<Floating>>) -> Self {
+    fn new(
+        clock: AnyPin<Input<Floating>>,
+        data: AnyPin<Input<Floating>>,
+        events: Producer<TurnEvent, EVENTS_CAPACITY>,
+    ) -> Self {
+        let decoder = QuadratureDecoder::new(StepsPerDetent::Four, clock.is_high(), data.is_high());
+
         Self {
             clock,
             data,
-            turn: None,
+            decoder,
+            events,
         }
     }
 
-    fn notify_turn(&mut self) {
-        self.turn = if self.clock.is_high() == self.data.is_high() {
-            Some(Turn::Right)
-        } else {
-            Some(Turn::Left)
-        }
-    }
+    fn notify_turn(&mut self, now: u64) {
+        self.clock.clear_interrupt();
+        self.data.clear_interrupt();
 
-    fn check_turn(&mut self) -> Option<Turn> {
-        self.turn.take()
+        if let Some(turn) = self.decoder.update(self.clock.is_high(), self.data.is_high()) {
+            // counted as dropped when the main loop doesn't keep up
+            self.events.push(TurnEvent { turn, at: now }).ok();
+        }
     }
 }
 
 static ENCODER: Mutex<RefCell<Option<RotaryEncoder>>> = Mutex::new(RefCell::new(None));
+static ENCODER_EVENTS: Channel<TurnEvent, EVENTS_CAPACITY> = Channel::new();
 
 #[entry]
 fn main() -> ! {
@@ -68,12 +76,14 @@ fn main() -> ! {
     // configure rotary encoder
     let mut clk_pin = io.pins.gpio0.into_floating_input();
     let mut dt_pin = io.pins.gpio1.into_floating_input();
-    // clk_pin.listen(Event::RisingEdge);
-    dt_pin.listen(Event::RisingEdge);
+    clk_pin.listen(Event::AnyEdge);
+    dt_pin.listen(Event::AnyEdge);
 
+    let (producer, mut events) = ENCODER_EVENTS.split();
     let encoder = RotaryEncoder::new(
         clk_pin.degrade().into(),
         dt_pin.degrade().into(),
+        producer,
     );
 
     critical_section::with(|cs| {
@@ -82,32 +92,35 @@ fn main() -> ! {
 
     println!("Coucou");
 
+    // setpoint moving faster as the encoder turns faster
+    let mut acceleration = Acceleration::default();
+    let mut setpoint: i32 = 0;
+    let mut dropped = 0;
+
     loop {
-        let turn = critical_section::with(|cs| {
-            if let Some(encoder) = ENCODER.borrow(cs).borrow_mut().as_mut() {
-                encoder.check_turn()
-            } else {
-                None
-            }
-        });
-
-        if let Some(turn) = turn {
-            if turn == Turn::Left {
-                println!("Left");
-            } else {
-                println!("Right");
-            }
+        let event = events.next_event_blocking();
+
+        setpoint = (setpoint + acceleration.apply(event.turn, event.at)).clamp(0, 1000);
+        println!("{:?}, setpoint: {}", event.turn, setpoint);
+
+        if events.dropped() != dropped {
+            dropped = events.dropped();
+            println!("Dropped turns: {}", dropped);
         }
     }
 }
 
+fn now_ms() -> u64 {
+    SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000)
+}
+
 #[handler]
 fn encoder_interrupt() {
-    println!("Interrupt");
+    let now = now_ms();
     critical_section::with(|cs| {
         let mut encoder = ENCODER.borrow_ref_mut(cs);
         if let Some(encoder) = encoder.as_mut() {
-            encoder.notify_turn();
+            encoder.notify_turn(now);
         }
     })
 }
\ No newline at end of file
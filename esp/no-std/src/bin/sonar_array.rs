@@ -0,0 +1,89 @@
+#![no_std]
+#![no_main]
+
+use drivers::sonar::{Clock, Hcsr04Array, Hcsr04Config};
+use esp_backtrace as _;
+use esp_hal::{
+    clock::ClockControl, delay::Delay, gpio::IO, peripherals::Peripherals, prelude::*,
+    systimer::SystemTimer,
+};
+use esp_println::println;
+
+// time for the echoes of a sensor to fade out before the next one fires
+const GUARD_US: u32 = 20_000;
+const REPORT_PERIOD_MS: u64 = 500;
+const NAMES: [&str; 3] = ["left", "front", "right"];
+
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTimer::now()
+    }
+
+    fn ticks_per_second(&self) -> u32 {
+        SystemTimer::TICKS_PER_SECOND as u32
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take();
+    let system = peripherals.SYSTEM.split();
+
+    let clocks = ClockControl::max(system.clock_control).freeze();
+    let delay = Delay::new(&clocks);
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    // setup the sensors, left to right
+    let triggers = [
+        io.pins.gpio1.into_push_pull_output().degrade(),
+        io.pins.gpio3.into_push_pull_output().degrade(),
+        io.pins.gpio5.into_push_pull_output().degrade(),
+    ];
+    let echoes = [
+        io.pins.gpio0.into_floating_input().degrade(),
+        io.pins.gpio2.into_floating_input().degrade(),
+        io.pins.gpio4.into_floating_input().degrade(),
+    ];
+    let mut sonars = Hcsr04Array::new(
+        triggers,
+        echoes,
+        SystemClock,
+        delay,
+        Hcsr04Config::DEFAULT,
+        GUARD_US,
+    );
+
+    let mut reported_at = now_ms();
+
+    loop {
+        sonars.poll().unwrap();
+
+        let now = now_ms();
+        if now - reported_at < REPORT_PERIOD_MS {
+            continue;
+        }
+        reported_at = now;
+
+        let array = sonars.array();
+        for (name, reading) in NAMES.iter().zip(array.readings()) {
+            match reading.map(|reading| reading.result) {
+                Some(Ok(distance)) => println!("{}: {}", name, distance),
+                Some(Err(error)) => println!("{}: {:?}", name, error),
+                None => println!("{}: -", name),
+            }
+        }
+        if let Some((sensor, distance)) = array.closest() {
+            println!("Closest obstacle: {} on the {}", distance, NAMES[sensor]);
+        }
+        for sensor in array.timed_out() {
+            println!("No echo on the {}", NAMES[sensor]);
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000)
+}
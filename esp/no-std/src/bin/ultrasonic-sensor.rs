@@ -1,10 +1,57 @@
 #![no_std]
 #![no_main]
 
+use drivers::sonar::{
+    filter::{Estimate, Filter, Median, OutlierRejection},
+    Beeper, Clock, Distance, Hcsr04, Hcsr04Config, Pattern, Zone, ZoneAlert,
+};
 use esp_backtrace as _;
 use esp_hal::{clock::ClockControl, delay::Delay, gpio::IO, peripherals::Peripherals, prelude::*, rtc_cntl::Rtc, systimer::SystemTimer};
 use esp_println::println;
 
+// air temperature in tenths of °C, sets the speed of sound
+const AIR_TEMPERATURE: i16 = 200;
+// time for the echoes of a measurement to fade out before the next one
+const MEASURE_PERIOD_MS: u64 = 60;
+// how far past the bound of a zone the obstacle goes before leaving it
+const HYSTERESIS_MM: u32 = 5;
+
+const BLINK: Pattern = Pattern::Blink {
+    on_ms: 100,
+    off_ms: 100,
+};
+// red, yellow and green LEDs
+const ZONES: [Zone<3>; 4] = [
+    Zone {
+        max: Distance::from_mm(49),
+        outputs: [BLINK, Pattern::Off, Pattern::Off],
+    },
+    Zone {
+        max: Distance::from_cm(10),
+        outputs: [Pattern::On, Pattern::Off, Pattern::Off],
+    },
+    Zone {
+        max: Distance::from_cm(20),
+        outputs: [Pattern::Off, Pattern::On, Pattern::Off],
+    },
+    Zone {
+        max: Distance::MAX,
+        outputs: [Pattern::Off, Pattern::Off, Pattern::On],
+    },
+];
+
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTimer::now()
+    }
+
+    fn ticks_per_second(&self) -> u32 {
+        SystemTimer::TICKS_PER_SECOND as u32
+    }
+}
+
 #[entry]
 fn main() -> ! {
     let peripherals = Peripherals::take();
@@ -15,62 +62,73 @@ fn main() -> ! {
 
     let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
 
-    // setup ultrasonic sensor pins
-    let mut trig = io.pins.gpio1.into_push_pull_output();
+    // setup ultrasonic sensor
+    let trig = io.pins.gpio1.into_push_pull_output();
     let echo = io.pins.gpio0.into_floating_input();
-
-    // setup LED pins
-    let mut red_led = io.pins.gpio4.into_push_pull_output();
-    let mut yellow_led = io.pins.gpio5.into_push_pull_output();
-    let mut green_led = io.pins.gpio6.into_push_pull_output();
+    let mut sonar = Hcsr04::new(trig, echo, SystemClock, delay).with_config(Hcsr04Config {
+        temperature: AIR_TEMPERATURE,
+        ..Hcsr04Config::DEFAULT
+    });
+
+    // drop lost waves, then smooth the jitter
+    let mut filter = OutlierRejection::new(100.0, 3).then(Median::<3>::new());
+
+    // setup LED pins, red to green, and the buzzer, which may be left out
+    let mut leds = [
+        io.pins.gpio4.into_push_pull_output().degrade(),
+        io.pins.gpio5.into_push_pull_output().degrade(),
+        io.pins.gpio6.into_push_pull_output().degrade(),
+    ];
+    let mut buzzer = io.pins.gpio7.into_push_pull_output();
+
+    let mut alert = ZoneAlert::new(&ZONES, Distance::from_mm(HYSTERESIS_MM));
+    let mut beeper = Beeper::new(Distance::from_cm(5), Distance::from_cm(40), 50, 100, 800);
+    let mut measured_at = 0;
 
     println!("Hello world!");
 
     loop {
-        trig.set_low();
-        delay.delay_micros(5u32);
-
-        // trigger the sensor
-        trig.set_high();
-        delay.delay_micros(10u32);
-        trig.set_low();
-
-        // wait for the echo to go high
-        while !echo.is_high() {}
-
-        // kick off timer measurement
-        let echo_start = SystemTimer::now();
-
-        // wait for the echo to go low
-        while !echo.is_low() {}
-
-        // collect current timer count
-        let echo_end = SystemTimer::now();
-
-        // calculate the elapsed timer count
-        let echo_duration = echo_end.wrapping_sub(echo_start);
-
-        // calculate the distance in cm
-        let distance_cm = echo_duration / 16 / 58;
-
-        // turn on the LED based on distance
-        if distance_cm < 5 {
-            red_led.toggle();
-            delay.delay_millis(100u32);
-        } else if distance_cm <= 10 {
-            red_led.set_high();
-            yellow_led.set_low();
-            green_led.set_low();
-        } else if distance_cm <= 20 {
-            yellow_led.set_high();
-            red_led.set_low();
-            green_led.set_low();
+        // the LEDs and the buzzer keep blinking while the wave travels
+        let now = now_ms();
+        for (led, on) in leds.iter_mut().zip(alert.outputs(now)) {
+            if on {
+                led.set_high();
+            } else {
+                led.set_low();
+            }
+        }
+        if beeper.is_on(now) {
+            buzzer.set_high();
         } else {
-            green_led.set_high();
-            red_led.set_low();
-            yellow_led.set_low();
+            buzzer.set_low();
+        }
+
+        if !sonar.is_busy() && now - measured_at >= MEASURE_PERIOD_MS {
+            measured_at = now;
+            sonar.trigger().unwrap();
         }
 
-        println!("Distance: {} cm", distance_cm);
+        let reading = match sonar.poll() {
+            None => continue,
+            Some(Ok(distance)) => distance,
+            Some(Err(error)) => {
+                println!("Measurement failed: {:?}", error);
+                continue;
+            }
+        };
+        let Some(estimate) = filter.update(Estimate::from(reading)) else {
+            println!("Outlier dropped: {}", reading);
+            continue;
+        };
+        let distance = Distance::from_mm(estimate.value as u32);
+
+        alert.update(distance, now);
+        beeper.set_distance(distance);
+
+        println!("Distance: {}", distance);
     }
-}
\ No newline at end of file
+}
+
+fn now_ms() -> u64 {
+    SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000)
+}